@@ -0,0 +1,136 @@
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::vec::Point;
+
+/// Axis-aligned bounding box, one interval per axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        x: Interval::EMPTY,
+        y: Interval::EMPTY,
+        z: Interval::EMPTY,
+    };
+
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        Aabb { x, y, z }
+    }
+
+    /// Box having `a` and `b` as opposite corners
+    pub fn from_points(a: &Point, b: &Point) -> Self {
+        let axis = |a: f64, b: f64| Interval::new(a.min(b), a.max(b));
+        Aabb::new(axis(a.x, b.x), axis(a.y, b.y), axis(a.z, b.z))
+    }
+
+    /// Smallest box enclosing both `a` and `b`
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Self {
+        let axis = |a: &Interval, b: &Interval| Interval::new(a.min.min(b.min), a.max.max(b.max));
+        Aabb::new(axis(&a.x, &b.x), axis(&a.y, &b.y), axis(&a.z, &b.z))
+    }
+
+    /// Both boxes share some volume, or at least a face
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.x.overlaps(&other.x) && self.y.overlaps(&other.y) && self.z.overlaps(&other.z)
+    }
+
+    /// `ray` goes through the box within `t_range`, following the slab
+    /// method. A flat box is hit when the ray crosses its plane.
+    pub fn hit(&self, ray: &Ray, t_range: Interval) -> bool {
+        let (mut t_min, mut t_max) = (t_range.min, t_range.max);
+        let slabs = [
+            (&self.x, ray.origin.x, ray.direction.x),
+            (&self.y, ray.origin.y, ray.direction.y),
+            (&self.z, ray.origin.z, ray.direction.z),
+        ];
+        for (axis, origin, dir) in slabs.iter() {
+            let inv_dir = 1.0 / dir;
+            let t0 = (axis.min - origin) * inv_dir;
+            let t1 = (axis.max - origin) * inv_dir;
+            let (t0, t1) = if inv_dir < 0.0 { (t1, t0) } else { (t0, t1) };
+            // a ray parallel to the slab and starting on its edge gives NaN,
+            // which max and min ignore
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn min(&self) -> Point {
+        Point::new(self.x.min, self.y.min, self.z.min)
+    }
+
+    pub fn max(&self) -> Point {
+        Point::new(self.x.max, self.y.max, self.z.max)
+    }
+
+    /// Every side is at a finite distance, empty boxes are not bounded
+    pub fn is_bounded(&self) -> bool {
+        [self.x, self.y, self.z]
+            .iter()
+            .all(|i| i.min.is_finite() && i.max.is_finite())
+    }
+
+    pub fn center(&self) -> Point {
+        (self.min() + self.max()) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn surrounding_encloses_both() {
+        let a = Aabb::from_points(&Point::new(1.0, 0.0, 0.0), &Point::new(0.0, 1.0, 1.0));
+        assert_eq!(Interval::new(0.0, 1.0), a.x);
+        let b = Aabb::from_points(&Point::new(-2.0, 0.5, 0.5), &Point::new(-1.0, 3.0, 0.5));
+        let both = Aabb::surrounding(&a, &b);
+        assert_eq!(Point::new(-2.0, 0.0, 0.0), both.min());
+        assert_eq!(Point::new(1.0, 3.0, 1.0), both.max());
+        assert_eq!(both, Aabb::surrounding(&both, &Aabb::EMPTY));
+        assert_eq!(Point::new(-0.5, 1.5, 0.5), both.center());
+        assert!(both.is_bounded());
+        assert!(!Aabb::EMPTY.is_bounded());
+        let slab = Aabb::new(
+            Interval::UNIVERSE,
+            Interval::new(0.0, 1.0),
+            Interval::UNIVERSE,
+        );
+        assert!(!slab.is_bounded());
+    }
+    #[test]
+    fn slab_hits() {
+        use crate::vec::Vector;
+        let unit = Aabb::from_points(&Point::new(0.0, 0.0, 0.0), &Point::new(1.0, 1.0, 1.0));
+        let ahead = Interval::new(0.0, f64::MAX);
+        let through = Ray::new(Point::new(-1.0, 0.5, 0.5), Vector::new(1.0, 0.1, -0.1));
+        assert!(unit.hit(&through, ahead));
+        // the box is behind, or farther than the range
+        assert!(!unit.hit(&through, Interval::new(-5.0, 0.5)));
+        let beside = Ray::new(Point::new(-1.0, 2.0, 0.5), Vector::new(1.0, 0.0, 0.0));
+        assert!(!unit.hit(&beside, ahead));
+        let away = Ray::new(Point::new(-1.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0));
+        assert!(!unit.hit(&away, ahead));
+    }
+    #[test]
+    fn flat_box_is_hit_across() {
+        use crate::vec::Vector;
+        let flat = Aabb::from_points(&Point::new(0.0, 0.0, 2.0), &Point::new(1.0, 1.0, 2.0));
+        let ahead = Interval::new(0.0, f64::MAX);
+        let across = Ray::new(Point::new(0.5, 0.5, 0.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(flat.hit(&across, ahead));
+        let slanted = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.25, 0.25, 1.0));
+        assert!(flat.hit(&slanted, ahead));
+        let parallel = Ray::new(Point::new(-1.0, 0.5, 1.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(!flat.hit(&parallel, ahead));
+        let outside = Ray::new(Point::new(2.0, 0.5, 0.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(!flat.hit(&outside, ahead));
+    }
+}
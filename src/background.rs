@@ -0,0 +1,118 @@
+use crate::error::RenderError;
+use crate::image::{colors, Color};
+use crate::ray::Ray;
+use crate::texture::{ImageTexture, Texture};
+use crate::vec;
+
+/// Light coming from everything the rays miss
+pub trait Background: std::fmt::Debug + Send + Sync {
+    fn sample(&self, ray: &Ray) -> Color;
+}
+
+/// Vertical gradient, from `bottom` for rays going down to `top` for rays
+/// going up
+#[derive(Debug, Clone, Copy)]
+pub struct GradientSky {
+    pub top: Color,
+    pub bottom: Color,
+}
+
+impl std::default::Default for GradientSky {
+    /// Blue sky fading to white at the horizon and below
+    fn default() -> Self {
+        GradientSky {
+            top: Color::new(0.5, 0.7, 1.0),
+            bottom: colors::WHITE,
+        }
+    }
+}
+
+impl Background for GradientSky {
+    fn sample(&self, ray: &Ray) -> Color {
+        let unit_dir = vec::unit(&ray.direction);
+        let t = 0.5 * (unit_dir.y + 1.0);
+        self.bottom.lerp(&self.top, t)
+    }
+}
+
+/// Same color in every direction
+#[derive(Debug, Clone, Copy)]
+pub struct SolidBackground(pub Color);
+
+impl Background for SolidBackground {
+    fn sample(&self, _ray: &Ray) -> Color {
+        self.0
+    }
+}
+
+/// Equirectangular picture surrounding the scene, the center of the
+/// picture seen along +x and its top along +y
+#[derive(Debug)]
+pub struct EnvironmentMap {
+    picture: ImageTexture,
+}
+
+impl EnvironmentMap {
+    pub fn open(path: &str) -> Result<Self, RenderError> {
+        Ok(Self::new(ImageTexture::open(path)?))
+    }
+
+    pub fn new(picture: ImageTexture) -> Self {
+        EnvironmentMap { picture }
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn sample(&self, ray: &Ray) -> Color {
+        use std::f64::consts::PI;
+        let d = vec::unit(&ray.direction);
+        // longitude wraps around the seam behind the picture, latitude goes
+        // from the top of the picture for +y to its bottom for -y
+        let u = (0.5 + d.z.atan2(d.x) / (2.0 * PI)).rem_euclid(1.0);
+        let v = 0.5 - d.y.asin() / PI;
+        // texture coordinates go up
+        self.picture.value(u, 1.0 - v, &d)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec::{Point, Vector};
+    #[test]
+    fn black_background_is_black() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.3, 0.2, -1.0));
+        let c = SolidBackground(colors::BLACK).sample(&ray);
+        assert_eq!((0.0, 0.0, 0.0), (c.red, c.green, c.blue));
+    }
+
+    #[test]
+    fn gradient_ends() {
+        let sky = GradientSky::default();
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let down = sky.sample(&Ray::new(origin, Vector::new(0.0, -2.0, 0.0)));
+        assert_eq!((1.0, 1.0, 1.0), (down.red, down.green, down.blue));
+        let up = sky.sample(&Ray::new(origin, Vector::new(0.0, 3.0, 0.0)));
+        assert_eq!((0.5, 0.7, 1.0), (up.red, up.green, up.blue));
+    }
+
+    #[test]
+    fn environment_directions() {
+        let pixel = |i: usize| Color::new(i as f64, 0.0, 0.0);
+        // pixel index in red, top row 0 1 2 3, bottom row 4 5 6 7
+        let picture = ImageTexture::from_colors(4, 2, (0..8).map(pixel).collect());
+        let env = EnvironmentMap::new(picture);
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let sample = |x, y, z| env.sample(&Ray::new(origin, Vector::new(x, y, z))).red;
+        assert_eq!(6.0, sample(1.0, 0.0, 0.0));
+        // right on the seam
+        assert_eq!(4.0, sample(-1.0, 0.0, 0.0));
+        assert_eq!(4.0, sample(-1.0, 0.0, -1e-9));
+        assert_eq!(7.0, sample(-1.0, 0.0, 1e-9));
+        assert_eq!(7.0, sample(0.0, 0.0, 1.0));
+        assert_eq!(5.0, sample(0.0, 0.0, -1.0));
+        assert_eq!(2.0, sample(0.0, 1.0, 0.0));
+        assert_eq!(3.0, sample(0.0, 0.5, 1.0));
+        assert_eq!(6.0, sample(0.0, -1.0, 0.0));
+    }
+}
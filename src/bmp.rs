@@ -0,0 +1,85 @@
+use crate::image::Image;
+use std::io;
+
+// sizes of BITMAPFILEHEADER and BITMAPINFOHEADER
+const FILE_HEADER_SIZE: u32 = 14;
+const INFO_HEADER_SIZE: u32 = 40;
+
+/// Write `img` as an uncompressed 24 bits BMP, colors are expected in [0, 1)
+pub fn write_bmp<W: io::Write>(mut writer: W, img: &Image) -> io::Result<()> {
+    // rows are padded to a multiple of 4 bytes
+    let row_size = (3 * img.width).div_ceil(4) * 4;
+    let data_size = (row_size * img.height) as u32;
+    let offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE;
+    let mut header = Vec::with_capacity(offset as usize);
+    header.extend_from_slice(b"BM");
+    header.extend_from_slice(&(offset + data_size).to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&offset.to_le_bytes());
+    header.extend_from_slice(&INFO_HEADER_SIZE.to_le_bytes());
+    // a positive height means rows are stored bottom to top
+    header.extend_from_slice(&(img.width as i32).to_le_bytes());
+    header.extend_from_slice(&(img.height as i32).to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&24u16.to_le_bytes());
+    // no compression
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&data_size.to_le_bytes());
+    // 72 DPI in pixels per meter, on both axes
+    header.extend_from_slice(&2835i32.to_le_bytes());
+    header.extend_from_slice(&2835i32.to_le_bytes());
+    // no palette
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    writer.write_all(&header)?;
+    let mut row = Vec::with_capacity(row_size);
+    for l in (0..img.height).rev() {
+        row.clear();
+        for px in &img.data[l * img.width..(l + 1) * img.width] {
+            let [r, g, b] = px.to_rgb8();
+            row.extend_from_slice(&[b, g, r]);
+        }
+        row.resize(row_size, 0);
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+
+    fn u32_at(data: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+    }
+
+    #[test]
+    fn header_and_pixels() {
+        let mut img = Image::new(3, 2);
+        for (i, px) in img.data.iter_mut().enumerate() {
+            let i = i as f64;
+            *px = Color::new(i / 8.0, 0.5, 0.999 - i / 8.0);
+        }
+        let mut out = Vec::new();
+        write_bmp(&mut out, &img).unwrap();
+        // 9 bytes rows padded to 12
+        assert_eq!(b"BM", &out[..2]);
+        assert_eq!(54 + 2 * 12, out.len());
+        assert_eq!(out.len() as u32, u32_at(&out, 2));
+        assert_eq!(54, u32_at(&out, 10));
+        assert_eq!(3, u32_at(&out, 18));
+        assert_eq!(2, u32_at(&out, 22));
+        assert_eq!(24, u16::from_le_bytes([out[28], out[29]]));
+        for line in 0..2 {
+            // bottom row first
+            let row = &out[54 + (1 - line) * 12..54 + (2 - line) * 12];
+            assert_eq!([0, 0, 0], row[9..]);
+            for col in 0..3 {
+                let [r, g, b] = img.data[line * 3 + col].to_rgb8();
+                assert_eq!([b, g, r], row[3 * col..3 * col + 3]);
+            }
+        }
+        assert_eq!([255, 128, 0], out[54 + 12..54 + 15]);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::{HitRecord, Hittable, Ray};
+use crate::vec::{self, Point, Vector};
+
+/// Axis-aligned box between two opposite corners
+#[derive(Debug)]
+pub struct BoxPrim {
+    pub min: Point,
+    pub max: Point,
+    pub material: Box<dyn Material>,
+}
+
+impl BoxPrim {
+    /// Box having `a` and `b` as opposite corners
+    pub fn new(a: Point, b: Point, material: Box<dyn Material>) -> Self {
+        BoxPrim {
+            min: Point::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Point::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+            material,
+        }
+    }
+}
+
+impl Hittable for BoxPrim {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        // the ray is inside the box once it entered the three slabs and
+        // until it leaves one of them, the axis of the last slab entered and
+        // of the first slab left tell which face is hit
+        let (mut t_in, mut axis_in) = (f64::NEG_INFINITY, 0);
+        let (mut t_out, mut axis_out) = (f64::INFINITY, 0);
+        for axis in 0..3 {
+            let inv_dir = 1.0 / ray.direction[axis];
+            let t0 = (self.min[axis] - ray.origin[axis]) * inv_dir;
+            let t1 = (self.max[axis] - ray.origin[axis]) * inv_dir;
+            let (t0, t1) = if inv_dir < 0.0 { (t1, t0) } else { (t0, t1) };
+            if t0 > t_in {
+                t_in = t0;
+                axis_in = axis;
+            }
+            if t1 < t_out {
+                t_out = t1;
+                axis_out = axis;
+            }
+        }
+        if t_in > t_out {
+            return None;
+        }
+        let (t, axis) = if t_range.surrounds(t_in) {
+            (t_in, axis_in)
+        } else if t_range.surrounds(t_out) {
+            (t_out, axis_out)
+        } else {
+            return None;
+        };
+        let point = ray.at(t);
+        // outward normal of the face on the side of the point
+        let side = if point[axis] > (self.min[axis] + self.max[axis]) / 2.0 {
+            1.0
+        } else {
+            -1.0
+        };
+        let mut normal = Vector::new(0.0, 0.0, 0.0);
+        normal[axis] = side;
+        // ray direction and normal point the same way if dot product is positive
+        let front = vec::dot(&normal, &ray.direction) < 0.0;
+        Some(HitRecord::new(point, normal, t, front, &self.material))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(&self.min, &self.max)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::test::gray;
+
+    fn unit_box() -> BoxPrim {
+        BoxPrim::new(Point::new(1.0, 1.0, 1.0), Point::new(0.0, 0.0, 0.0), gray())
+    }
+
+    #[test]
+    fn hit_faces_have_outward_normals() {
+        let cube = unit_box();
+        let ahead = Interval::new(0.001, f64::MAX);
+        let ray = Ray::new(Point::new(3.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0));
+        let hit = cube.hit(&ray, ahead).unwrap();
+        assert_eq!(2.0, hit.t);
+        assert!(hit.front_face);
+        assert_eq!(Vector::new(1.0, 0.0, 0.0), hit.normal);
+        let ray = Ray::new(Point::new(0.5, -1.0, 0.25), Vector::new(0.0, 1.0, 0.5));
+        let hit = cube.hit(&ray, ahead).unwrap();
+        assert_eq!(Vector::new(0.0, -1.0, 0.0), hit.normal);
+        // leaving through +x from inside, the normal faces the ray
+        let ray = Ray::new(Point::new(0.5, 0.5, 0.5), Vector::new(1.0, 0.0, 0.0));
+        let hit = cube.hit(&ray, ahead).unwrap();
+        assert_eq!(0.5, hit.t);
+        assert!(!hit.front_face);
+        assert_eq!(Vector::new(-1.0, 0.0, 0.0), hit.normal);
+    }
+
+    #[test]
+    fn missed_or_out_of_range_box() {
+        let cube = unit_box();
+        let ahead = Interval::new(0.001, f64::MAX);
+        let beside = Ray::new(Point::new(3.0, 1.5, 0.5), Vector::new(-1.0, 0.0, 0.0));
+        assert!(cube.hit(&beside, ahead).is_none());
+        let away = Ray::new(Point::new(3.0, 0.5, 0.5), Vector::new(1.0, 0.0, 0.0));
+        assert!(cube.hit(&away, ahead).is_none());
+        let toward = Ray::new(Point::new(3.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0));
+        assert!(cube.hit(&toward, Interval::new(0.001, 1.5)).is_none());
+        let far = cube.hit(&toward, Interval::new(2.5, f64::MAX)).unwrap();
+        assert_eq!(3.0, far.t);
+    }
+}
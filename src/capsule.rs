@@ -0,0 +1,178 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::{HitRecord, Hittable, Ray};
+use crate::vec::{self, Point, Vector};
+
+/// Cylinder of axis [a, b] closed by two hemispheres
+#[derive(Debug)]
+pub struct Capsule {
+    pub a: Point,
+    pub b: Point,
+    pub radius: f64,
+    pub material: Box<dyn Material>,
+}
+
+impl Capsule {
+    pub fn new(a: Point, b: Point, radius: f64, material: Box<dyn Material>) -> Self {
+        Capsule {
+            a,
+            b,
+            radius,
+            material,
+        }
+    }
+
+    // roots of the ray / sphere of center `center` intersection
+    fn sphere_roots(&self, ray: &Ray, center: &Point) -> Option<(f64, f64)> {
+        let oc = ray.origin - center;
+        let a = ray.direction.length_squared();
+        let half_b = vec::dot(&ray.direction, &oc);
+        let c = oc.length_squared() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let discr_sqrt = discriminant.sqrt();
+        Some(((-half_b - discr_sqrt) / a, (-half_b + discr_sqrt) / a))
+    }
+
+    // roots of the ray / infinite cylinder of axis `axis` intersection
+    fn body_roots(&self, ray: &Ray, axis: &Vector) -> Option<(f64, f64)> {
+        // only the components perpendicular to the axis matter
+        let oa = ray.origin - self.a;
+        let dir = ray.direction - vec::dot(&ray.direction, axis) * axis;
+        let oc = oa - vec::dot(&oa, axis) * axis;
+        let a = dir.length_squared();
+        if a < 1e-12 {
+            // parallel to the axis, only the caps can be hit
+            return None;
+        }
+        let half_b = vec::dot(&dir, &oc);
+        let c = oc.length_squared() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let discr_sqrt = discriminant.sqrt();
+        Some(((-half_b - discr_sqrt) / a, (-half_b + discr_sqrt) / a))
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        // the capsule is the union of the body between a and b and of two
+        // spheres centered on a and b. A candidate is kept if it lies on the
+        // part of the surface it belongs to: its height along the axis is
+        // within [0, length] for the body, below 0 or above length for caps.
+        let ab = self.b - self.a;
+        let length = ab.length();
+        let axis = ab / length;
+        let height = |t: f64| vec::dot(&(ray.at(t) - self.a), &axis);
+        let mut closest: Option<(f64, Vector)> = None;
+        let mut consider = |t: f64, normal: &dyn Fn(&Point) -> Vector| {
+            let nearer = match closest {
+                Some((c, _)) => t < c,
+                None => true,
+            };
+            if t_range.surrounds(t) && nearer {
+                closest = Some((t, normal(&ray.at(t))));
+            }
+        };
+        if let Some((t0, t1)) = self.body_roots(ray, &axis) {
+            for &t in &[t0, t1] {
+                let h = height(t);
+                if h >= 0.0 && h <= length {
+                    consider(t, &|p| (p - (self.a + h * axis)) / self.radius);
+                }
+            }
+        }
+        for (center, below) in &[(self.a, true), (self.b, false)] {
+            if let Some((t0, t1)) = self.sphere_roots(ray, center) {
+                for &t in &[t0, t1] {
+                    let h = height(t);
+                    if (*below && h < 0.0) || (!*below && h > length) {
+                        consider(t, &|p| (p - center) / self.radius);
+                    }
+                }
+            }
+        }
+        closest.map(|(t, normal)| {
+            // ray direction and normal point the same way if dot product is positive
+            let front = vec::dot(&normal, &ray.direction) < 0.0;
+            HitRecord::new(ray.at(t), normal, t, front, &self.material)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // enclosing both end spheres encloses the body
+        let r = Vector::splat(self.radius);
+        Aabb::surrounding(
+            &Aabb::from_points(&(self.a - r), &(self.a + r)),
+            &Aabb::from_points(&(self.b - r), &(self.b + r)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::test::gray;
+
+    fn capsule() -> Capsule {
+        Capsule::new(
+            Point::new(0.0, -1.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+            0.5,
+            gray(),
+        )
+    }
+
+    fn assert_unit(v: &Vector) {
+        assert!((v.length() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn body_hit() {
+        let capsule = capsule();
+        let ray = Ray::new(Point::new(-5.0, 0.3, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let hit = capsule.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-9);
+        assert!(hit.normal.approx_eq(&Vector::new(-1.0, 0.0, 0.0), 1e-9));
+        assert!(hit.front_face);
+        assert_unit(&hit.normal);
+    }
+
+    #[test]
+    fn end_cap_hit() {
+        let capsule = capsule();
+        let ray = Ray::new(Point::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        let hit = capsule.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!((hit.t - 3.5).abs() < 1e-9);
+        assert!(hit.normal.approx_eq(&Vector::new(0.0, 1.0, 0.0), 1e-9));
+        assert_unit(&hit.normal);
+    }
+
+    #[test]
+    fn ray_through_waist() {
+        let capsule = capsule();
+        // grazes the top cap just above the body
+        let ray = Ray::new(Point::new(-5.0, 1.2, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let hit = capsule.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        let x = -(0.25f64 - 0.04).sqrt();
+        assert!((hit.point.x - x).abs() < 1e-9);
+        assert!(hit
+            .normal
+            .approx_eq(&Vector::new(x / 0.5, 0.2 / 0.5, 0.0), 1e-9));
+        assert_unit(&hit.normal);
+        // crosses the whole body and leaves through the far side
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(1.0, 1.0, 0.0));
+        let hit = capsule.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!(!hit.front_face);
+        assert!((hit.point.x - 0.5).abs() < 1e-9);
+        assert_unit(&hit.normal);
+        // passes above the top cap
+        let ray = Ray::new(Point::new(-5.0, 1.6, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(capsule.hit(&ray, Interval::new(0.001, f64::MAX)).is_none());
+    }
+}
@@ -0,0 +1,87 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// What to do with the image rendered so far
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckpointAction {
+    Continue,
+    Save,
+    /// The render was interrupted, save what's there and stop
+    SaveAndStop,
+}
+
+/// Periodic and on-interrupt saves of a render in progress
+pub struct Checkpoint {
+    interval: Option<Duration>,
+    last_save: Instant,
+    interrupted: Arc<AtomicBool>,
+}
+
+impl Checkpoint {
+    /// Save every `interval` if any, and when interrupted
+    pub fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            last_save: Instant::now(),
+            interrupted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Interrupt the render on the first Ctrl-C, a second one kills the
+    /// process right away
+    #[cfg(feature = "ctrlc")]
+    pub fn catch_interrupt(&self) -> Result<(), ctrlc::Error> {
+        let interrupted = self.interrupted.clone();
+        ctrlc::set_handler(move || {
+            if interrupted.swap(true, Ordering::SeqCst) {
+                std::process::exit(130);
+            }
+        })
+    }
+
+    // stands for the Ctrl-C handler
+    #[cfg(test)]
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::SeqCst);
+    }
+
+    /// Action to take now, called between two units of work
+    pub fn poll(&mut self) -> CheckpointAction {
+        if self.interrupted.load(Ordering::SeqCst) {
+            return CheckpointAction::SaveAndStop;
+        }
+        match self.interval {
+            Some(interval) if self.last_save.elapsed() >= interval => {
+                self.last_save = Instant::now();
+                CheckpointAction::Save
+            }
+            _ => CheckpointAction::Continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn interrupt_wins_over_interval() {
+        let mut checkpoint = Checkpoint::new(None);
+        assert_eq!(CheckpointAction::Continue, checkpoint.poll());
+        checkpoint.interrupt();
+        assert_eq!(CheckpointAction::SaveAndStop, checkpoint.poll());
+        let mut checkpoint = Checkpoint::new(Some(Duration::from_secs(0)));
+        assert_eq!(CheckpointAction::Save, checkpoint.poll());
+        checkpoint.interrupt();
+        assert_eq!(CheckpointAction::SaveAndStop, checkpoint.poll());
+    }
+
+    #[test]
+    fn saves_once_per_interval() {
+        let mut checkpoint = Checkpoint::new(Some(Duration::from_secs(3600)));
+        assert_eq!(CheckpointAction::Continue, checkpoint.poll());
+        checkpoint.last_save -= Duration::from_secs(3600);
+        assert_eq!(CheckpointAction::Save, checkpoint.poll());
+        assert_eq!(CheckpointAction::Continue, checkpoint.poll());
+    }
+}
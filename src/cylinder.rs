@@ -0,0 +1,206 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::{HitRecord, Hittable, Ray};
+use crate::vec::{self, Point, Vector};
+
+/// Cylinder standing on the disk centered on `base`, going `height` along
+/// `axis`, closed by flat caps
+#[derive(Debug)]
+pub struct Cylinder {
+    pub base: Point,
+    /// unit vector
+    pub axis: Vector,
+    pub radius: f64,
+    pub height: f64,
+    pub material: Box<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Point,
+        axis: Vector,
+        radius: f64,
+        height: f64,
+        material: Box<dyn Material>,
+    ) -> Self {
+        Cylinder {
+            base,
+            axis: vec::unit(&axis),
+            radius,
+            height,
+            material,
+        }
+    }
+
+    // roots of the ray / infinite cylinder intersection
+    fn side_roots(&self, ray: &Ray) -> Option<(f64, f64)> {
+        // only the components perpendicular to the axis matter
+        let ob = ray.origin - self.base;
+        let dir = ray.direction - vec::dot(&ray.direction, &self.axis) * &self.axis;
+        let oc = ob - vec::dot(&ob, &self.axis) * &self.axis;
+        let a = dir.length_squared();
+        if a < 1e-12 {
+            // parallel to the axis, only the caps can be hit
+            return None;
+        }
+        let half_b = vec::dot(&dir, &oc);
+        let c = oc.length_squared() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let discr_sqrt = discriminant.sqrt();
+        Some(((-half_b - discr_sqrt) / a, (-half_b + discr_sqrt) / a))
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        // candidates on the side are kept if their height along the axis is
+        // within [0, height], those on the cap planes if they are within
+        // the radius of the cap center
+        let height = |t: f64| vec::dot(&(ray.at(t) - self.base), &self.axis);
+        let mut closest: Option<(f64, Vector)> = None;
+        let mut consider = |t: f64, normal: Vector| {
+            let nearer = match closest {
+                Some((c, _)) => t < c,
+                None => true,
+            };
+            if t_range.surrounds(t) && nearer {
+                closest = Some((t, normal));
+            }
+        };
+        if let Some((t0, t1)) = self.side_roots(ray) {
+            for &t in &[t0, t1] {
+                let h = height(t);
+                if h >= 0.0 && h <= self.height {
+                    let normal = (ray.at(t) - (self.base + h * self.axis)) / self.radius;
+                    consider(t, normal);
+                }
+            }
+        }
+        let along = vec::dot(&ray.direction, &self.axis);
+        if along != 0.0 {
+            let top = self.base + self.height * self.axis;
+            for (center, normal) in &[(self.base, -self.axis), (top, self.axis)] {
+                let t = vec::dot(&(*center - ray.origin), &self.axis) / along;
+                if (ray.at(t) - *center).length_squared() <= self.radius * self.radius {
+                    consider(t, *normal);
+                }
+            }
+        }
+        closest.map(|(t, normal)| {
+            // ray direction and normal point the same way if dot product is positive
+            let front = vec::dot(&normal, &ray.direction) < 0.0;
+            HitRecord::new(ray.at(t), normal, t, front, &self.material)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // a cap reaches radius * sqrt(1 - axis²) from its center along each
+        // world axis, enclosing both caps encloses the side
+        let extent = |a: f64| self.radius * (1.0 - a * a).max(0.0).sqrt();
+        let r = Vector::new(
+            extent(self.axis.x),
+            extent(self.axis.y),
+            extent(self.axis.z),
+        );
+        let top = self.base + self.height * self.axis;
+        Aabb::surrounding(
+            &Aabb::from_points(&(self.base - r), &(self.base + r)),
+            &Aabb::from_points(&(top - r), &(top + r)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::test::gray;
+
+    // upright, from y = -1 to y = 1
+    fn cylinder() -> Cylinder {
+        Cylinder::new(
+            Point::new(0.0, -1.0, 0.0),
+            Vector::new(0.0, 3.0, 0.0),
+            0.5,
+            2.0,
+            gray(),
+        )
+    }
+
+    #[test]
+    fn side_hit() {
+        let cylinder = cylinder();
+        let ray = Ray::new(Point::new(-5.0, 0.3, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let hit = cylinder.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-9);
+        assert!(hit.normal.approx_eq(&Vector::new(-1.0, 0.0, 0.0), 1e-9));
+        assert!(hit.front_face);
+        // above the top
+        let above = Ray::new(Point::new(-5.0, 1.5, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(cylinder
+            .hit(&above, Interval::new(0.001, f64::MAX))
+            .is_none());
+    }
+
+    #[test]
+    fn cap_hit() {
+        let cylinder = cylinder();
+        let ray = Ray::new(Point::new(0.2, 5.0, 0.1), Vector::new(0.0, -1.0, 0.0));
+        let hit = cylinder.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-9);
+        assert!(hit.normal.approx_eq(&Vector::new(0.0, 1.0, 0.0), 1e-9));
+        assert!(hit.front_face);
+        // slanted through the bottom cap
+        let ray = Ray::new(Point::new(0.0, -3.0, 0.0), Vector::new(0.1, 1.0, 0.0));
+        let hit = cylinder.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-9);
+        assert!(hit.normal.approx_eq(&Vector::new(0.0, -1.0, 0.0), 1e-9));
+        // missing the cap beside the cylinder
+        let beside = Ray::new(Point::new(0.6, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert!(cylinder
+            .hit(&beside, Interval::new(0.001, f64::MAX))
+            .is_none());
+    }
+
+    #[test]
+    fn inside_ray_hits_far_wall() {
+        let cylinder = cylinder();
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = cylinder.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!((hit.t - 0.5).abs() < 1e-9);
+        assert!(!hit.front_face);
+        // the normal faces the ray
+        assert!(hit.normal.approx_eq(&Vector::new(0.0, 0.0, -1.0), 1e-9));
+        // entering from outside, the near wall is skipped past its t
+        let ray = Ray::new(Point::new(0.0, 0.0, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = cylinder.hit(&ray, Interval::new(2.0, f64::MAX)).unwrap();
+        assert!((hit.t - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tilted_bounding_box() {
+        let bbox = cylinder().bounding_box();
+        assert!(bbox.min().approx_eq(&Point::new(-0.5, -1.0, -0.5), 1e-12));
+        assert!(bbox.max().approx_eq(&Point::new(0.5, 1.0, 0.5), 1e-12));
+        // leaning 45 degrees between x and y, the caps reach r * sqrt(1 - n²)
+        // along x and y but the full radius along z
+        let leaning = Cylinder::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 0.0),
+            0.5,
+            2.0,
+            gray(),
+        );
+        let (slant, reach) = (0.5 * 0.5f64.sqrt(), 2.0f64.sqrt());
+        let bbox = leaning.bounding_box();
+        assert!(bbox
+            .min()
+            .approx_eq(&Point::new(-slant, -slant, -0.5), 1e-12));
+        assert!(bbox
+            .max()
+            .approx_eq(&Point::new(reach + slant, reach + slant, 0.5), 1e-12));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::image::{colors, Color, Image};
+
+/// Buffers of the first hit of each pixel guiding the denoiser, as
+/// computed by `fill_aovs`: albedo, encoded normal and depth
+pub struct Guides<'a> {
+    pub albedo: &'a Image,
+    pub normal: &'a Image,
+    pub depth: &'a Image,
+}
+
+// B3 spline, applied along both axes
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+// tolerance of each edge-stopping function, the color one is halved at
+// each iteration as the image gets smoother
+const SIGMA_COLOR: f64 = 0.5;
+const SIGMA_ALBEDO: f64 = 0.1;
+const SIGMA_NORMAL: f64 = 0.1;
+// relative to the farthest of the two depths
+const SIGMA_DEPTH: f64 = 0.1;
+
+fn distance_squared(a: &Color, b: &Color) -> f64 {
+    let d = a - b;
+    d.red * d.red + d.green * d.green + d.blue * d.blue
+}
+
+/// Edge-avoiding À-Trous wavelet filter (Dammertz et al.): each of the
+/// `iterations` blurs with a 5x5 kernel whose taps are twice as far apart
+/// as in the previous one, neighbors contributing less the more their
+/// color, albedo, normal or depth differ
+pub fn a_trous(img: &Image, guides: &Guides, iterations: u32) -> Image {
+    let (width, height) = (img.width as isize, img.height as isize);
+    let mut current = img.data.clone();
+    for i in 0..iterations {
+        let step = 1 << i;
+        let sigma_color = SIGMA_COLOR / step as f64;
+        let mut next = vec![colors::BLACK; current.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let p = (y * width + x) as usize;
+                let (zp, mut sum, mut weights) = (guides.depth.data[p].red, colors::BLACK, 0.0);
+                for (j, ky) in KERNEL.iter().enumerate() {
+                    let qy = y + (j as isize - 2) * step;
+                    if qy < 0 || qy >= height {
+                        continue;
+                    }
+                    for (k, kx) in KERNEL.iter().enumerate() {
+                        let qx = x + (k as isize - 2) * step;
+                        if qx < 0 || qx >= width {
+                            continue;
+                        }
+                        let q = (qy * width + qx) as usize;
+                        let zq = guides.depth.data[q].red;
+                        let depth = (zp - zq).abs() / zp.max(zq).max(1e-9);
+                        let exponent = distance_squared(&current[p], &current[q])
+                            / (sigma_color * sigma_color)
+                            + distance_squared(&guides.albedo.data[p], &guides.albedo.data[q])
+                                / (SIGMA_ALBEDO * SIGMA_ALBEDO)
+                            + distance_squared(&guides.normal.data[p], &guides.normal.data[q])
+                                / (SIGMA_NORMAL * SIGMA_NORMAL)
+                            + depth * depth / (SIGMA_DEPTH * SIGMA_DEPTH);
+                        let weight = ky * kx * (-exponent).exp();
+                        sum += weight * current[q];
+                        weights += weight;
+                    }
+                }
+                // the center pixel always has a positive weight
+                next[p] = &sum / weights;
+            }
+        }
+        current = next;
+    }
+    Image {
+        width: img.width,
+        height: img.height,
+        data: current,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // 16x8 image split in two flat noisy halves, at different depths and
+    // facing different directions
+    fn split_scene() -> (Image, Image, Image, Image) {
+        let mut rng = StdRng::seed_from_u64(7);
+        let (mut img, mut albedo) = (Image::new(16, 8), Image::new(16, 8));
+        let (mut normal, mut depth) = (Image::new(16, 8), Image::new(16, 8));
+        for index in 0..16 * 8 {
+            let left = index % 16 < 8;
+            let val = if left { 0.2 } else { 0.8 } + rng.gen_range(-0.1, 0.1);
+            img.data[index] = Color::new(val, val, val);
+            albedo.data[index] = Color::new(0.5, 0.5, 0.5);
+            normal.data[index] = if left {
+                Color::new(0.5, 0.5, 1.0)
+            } else {
+                Color::new(1.0, 0.5, 0.5)
+            };
+            let z = if left { 1.0 } else { 5.0 };
+            depth.data[index] = Color::new(z, z, z);
+        }
+        (img, albedo, normal, depth)
+    }
+
+    // variance of the left half interior, away from the edge
+    fn flat_variance(img: &Image) -> f64 {
+        let values: Vec<f64> = (0..img.data.len())
+            .filter(|i| i % 16 < 6)
+            .map(|i| img.data[i].red)
+            .collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / values.len() as f64
+    }
+
+    // mean difference between the columns on each side of the edge
+    fn edge_contrast(img: &Image) -> f64 {
+        (0..8)
+            .map(|line| img.data[line * 16 + 8].red - img.data[line * 16 + 7].red)
+            .sum::<f64>()
+            / 8.0
+    }
+
+    #[test]
+    fn more_iterations_smooth_more_and_keep_edges() {
+        let (img, albedo, normal, depth) = split_scene();
+        let guides = Guides {
+            albedo: &albedo,
+            normal: &normal,
+            depth: &depth,
+        };
+        let once = a_trous(&img, &guides, 1);
+        let five = a_trous(&img, &guides, 5);
+        assert!(flat_variance(&once) < flat_variance(&img));
+        assert!(flat_variance(&five) < flat_variance(&once));
+        assert!(edge_contrast(&once) > 0.5);
+        assert!(edge_contrast(&five) > 0.5);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::{HitRecord, Hittable, Ray};
+use crate::vec::{self, Point, Vector};
+
+// below this, the ray is considered parallel to the disk
+const GRAZING: f64 = 1e-9;
+
+/// Flat disk of `radius` around `center`, facing `normal`
+#[derive(Debug)]
+pub struct Disk {
+    pub center: Point,
+    pub normal: Vector,
+    pub radius: f64,
+    pub material: Box<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vector, radius: f64, material: Box<dyn Material>) -> Self {
+        Disk {
+            center,
+            normal: vec::unit(&normal),
+            radius,
+            material,
+        }
+    }
+
+    // u is the angle around the normal over a full turn, v the distance to
+    // the center over the radius
+    fn uv(&self, point: &Point) -> (f64, f64) {
+        use std::f64::consts::PI;
+        let (tangent, bitangent) = self.normal.build_orthonormal_basis();
+        let offset = point - &self.center;
+        let angle = vec::dot(&offset, &bitangent).atan2(vec::dot(&offset, &tangent));
+        ((angle + PI) / (2.0 * PI), offset.length() / self.radius)
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        // same as a plane, then keep the points close enough to the center
+        let denom = vec::dot(&ray.direction, &self.normal);
+        if denom.abs() < GRAZING {
+            return None;
+        }
+        let t = vec::dot(&(self.center - ray.origin), &self.normal) / denom;
+        if !t_range.surrounds(t) {
+            return None;
+        }
+        let point = ray.at(t);
+        if (point - self.center).length_squared() > self.radius * self.radius {
+            return None;
+        }
+        let (u, v) = self.uv(&point);
+        let front = denom < 0.0;
+        Some(HitRecord::new(point, self.normal, t, front, &self.material).with_uv(u, v))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // the disk reaches radius * sqrt(1 - normal²) from its center along
+        // each world axis, padded for disks lying in an axis plane
+        let extent = |n: f64| (self.radius * (1.0 - n * n).max(0.0).sqrt()).max(1e-4);
+        let r = Vector::new(
+            extent(self.normal.x),
+            extent(self.normal.y),
+            extent(self.normal.z),
+        );
+        Aabb::from_points(&(self.center - r), &(self.center + r))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::test::gray;
+
+    // facing +z at z = -2
+    fn disk() -> Disk {
+        Disk::new(
+            Point::new(0.0, 0.0, -2.0),
+            Vector::new(0.0, 0.0, 3.0),
+            1.0,
+            gray(),
+        )
+    }
+
+    #[test]
+    fn center_hit() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let disk = disk();
+        let hit = disk.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert_eq!(2.0, hit.t);
+        assert!(hit.front_face);
+        assert_eq!(Vector::new(0.0, 0.0, 1.0), hit.normal);
+        assert_eq!(0.0, hit.v);
+    }
+
+    #[test]
+    fn just_outside_the_edge_misses() {
+        let disk = disk();
+        let ahead = Interval::new(0.001, f64::MAX);
+        let inside = Ray::new(Point::new(0.999, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = disk.hit(&inside, ahead).unwrap();
+        assert!((hit.v - 0.999).abs() < 1e-12);
+        let outside = Ray::new(Point::new(0.0, 1.001, 0.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(disk.hit(&outside, ahead).is_none());
+        let grazing = Ray::new(Point::new(0.0, 0.0, -2.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(disk.hit(&grazing, ahead).is_none());
+    }
+
+    #[test]
+    fn back_face_hit() {
+        let ray = Ray::new(Point::new(0.5, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let disk = disk();
+        let hit = disk.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert_eq!(3.0, hit.t);
+        assert!(!hit.front_face);
+        assert_eq!(Vector::new(0.0, 0.0, -1.0), hit.normal);
+    }
+
+    #[test]
+    fn angle_wraps_around_the_normal() {
+        let disk = disk();
+        let ahead = Interval::new(0.001, f64::MAX);
+        let u = |x: f64, y: f64| {
+            let ray = Ray::new(Point::new(x, y, 0.0), Vector::new(0.0, 0.0, -1.0));
+            disk.hit(&ray, ahead).unwrap().u
+        };
+        let (a, b) = (u(0.5, 0.0), u(0.0, 0.5));
+        assert!((0.0..=1.0).contains(&a) && (0.0..=1.0).contains(&b));
+        // a quarter turn apart
+        let quarter = (b - a).rem_euclid(1.0);
+        assert!((quarter - 0.25).abs() < 1e-12 || (quarter - 0.75).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,51 @@
+use std::{fmt, io};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderError {
+    /// The image would have more than `max` pixels
+    ResolutionTooLarge {
+        width: usize,
+        height: usize,
+        max: usize,
+    },
+    /// The number of pixels doesn't fit in memory addresses
+    ResolutionOverflow { width: usize, height: usize },
+    /// No writer handles images with this extension
+    UnsupportedFormat(String),
+    /// Reading or writing a file failed
+    Io(String),
+    /// A file couldn't be read as an image
+    Decode(String),
+    /// A scene description couldn't be parsed
+    InvalidScene(String),
+    /// A mesh file couldn't be parsed
+    InvalidMesh(String),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::ResolutionTooLarge { width, height, max } => write!(
+                f,
+                "Resolution {}x{} exceeds the maximum of {} pixels",
+                width, height, max
+            ),
+            RenderError::ResolutionOverflow { width, height } => {
+                write!(f, "Resolution {}x{} is too large", width, height)
+            }
+            RenderError::UnsupportedFormat(ext) => write!(f, "Unsupported image format {}", ext),
+            RenderError::Io(e) => write!(f, "{}", e),
+            RenderError::Decode(e) => write!(f, "Invalid image: {}", e),
+            RenderError::InvalidScene(e) => write!(f, "Invalid scene: {}", e),
+            RenderError::InvalidMesh(e) => write!(f, "Invalid mesh: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<io::Error> for RenderError {
+    fn from(e: io::Error) -> Self {
+        RenderError::Io(e.to_string())
+    }
+}
@@ -0,0 +1,69 @@
+use crate::image::Image;
+use ::exr::prelude::{
+    Encoding, Image as ExrImage, LayerAttributes, SpecificChannels, Vec2, WritableImage,
+};
+use std::io;
+
+/// Write `img` as a single layer OpenEXR image with full float RGB channels,
+/// colors are written as they are, without any clamping or gamma correction
+pub fn write_exr<W: io::Write + io::Seek>(writer: W, img: &Image) -> io::Result<()> {
+    let channels = SpecificChannels::rgb(|pos: Vec2<usize>| {
+        let px = &img.data[pos.y() * img.width + pos.x()];
+        (px.red as f32, px.green as f32, px.blue as f32)
+    });
+    let layer = ::exr::prelude::Layer::new(
+        (img.width, img.height),
+        LayerAttributes::default(),
+        Encoding::FAST_LOSSLESS,
+        channels,
+    );
+    ExrImage::from_layer(layer)
+        .write()
+        .to_buffered(writer)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+    use ::exr::prelude::{ReadChannels, ReadLayers};
+
+    // decode (width, pixels top to bottom) from the first layer of an EXR
+    fn read_exr(data: Vec<u8>) -> (usize, Vec<(f32, f32, f32)>) {
+        let decoded = ::exr::prelude::read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .rgb_channels(
+                |size: Vec2<usize>, _| (size.width(), vec![(0.0, 0.0, 0.0); size.area()]),
+                |(width, pixels): &mut (usize, Vec<(f32, f32, f32)>),
+                 pos: Vec2<usize>,
+                 px: (f32, f32, f32)| pixels[pos.y() * *width + pos.x()] = px,
+            )
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(io::Cursor::new(data))
+            .unwrap();
+        decoded.layer_data.channel_data.pixels
+    }
+
+    #[test]
+    fn round_trip_keeps_hdr_values() {
+        let mut img = Image::new(3, 2);
+        img.data[1] = Color::new(0.25, 0.5, 0.75);
+        img.data[3] = Color::new(1.5, 12.0, 0.001);
+        img.data[5] = Color::new(250.0, 0.0, 3.25);
+        let mut data = io::Cursor::new(Vec::new());
+        write_exr(&mut data, &img).unwrap();
+        let (width, pixels) = read_exr(data.into_inner());
+        assert_eq!(3, width);
+        assert_eq!(6, pixels.len());
+        for (px, expected) in pixels.iter().zip(&img.data) {
+            let expected = [expected.red, expected.green, expected.blue];
+            for (val, exp) in [px.0, px.1, px.2].iter().zip(&expected) {
+                // within half float precision, 11 bits of mantissa
+                assert!((*val as f64 - exp).abs() <= exp.abs() / 2048.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::error::RenderError;
+use crate::image::Image;
+use ::image::codecs::hdr::HdrEncoder;
+use ::image::Rgb;
+use std::io;
+
+/// Write `img` as a Radiance RGBE picture, colors are written as they are,
+/// without any clamping or gamma correction
+pub fn write_hdr<W: io::Write>(writer: W, img: &Image) -> Result<(), RenderError> {
+    let data: Vec<Rgb<f32>> = img
+        .data
+        .iter()
+        .map(|px| Rgb([px.red as f32, px.green as f32, px.blue as f32]))
+        .collect();
+    HdrEncoder::new(writer)
+        .encode(&data, img.width, img.height)
+        .map_err(|e| RenderError::Io(e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+    use ::image::codecs::hdr::HdrDecoder;
+
+    #[test]
+    fn round_trip_keeps_hdr_values() {
+        let mut img = Image::new(3, 2);
+        for (i, px) in img.data.iter_mut().enumerate() {
+            let i = i as f64;
+            *px = Color::new(i * 1.5, 0.25 + i, 10.0 - i);
+        }
+        let mut out = Vec::new();
+        write_hdr(&mut out, &img).unwrap();
+        let decoder = HdrDecoder::new(io::Cursor::new(out)).unwrap();
+        let size = decoder.metadata();
+        assert_eq!((3, 2), (size.width, size.height));
+        let pixels = decoder.read_image_hdr().unwrap();
+        for (px, rgb) in img.data.iter().zip(&pixels) {
+            // RGBE shares an exponent between channels, keeping 8 bits of
+            // mantissa for the brightest one
+            let step = px.red.max(px.green).max(px.blue) / 128.0;
+            let close = |a: f32, b: f64| (a as f64 - b).abs() <= step;
+            assert!(close(rgb.0[0], px.red));
+            assert!(close(rgb.0[1], px.green));
+            assert!(close(rgb.0[2], px.blue));
+        }
+    }
+}
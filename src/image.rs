@@ -1,4 +1,10 @@
-use std::ops::{Add, Div, Mul};
+use crate::error::RenderError;
+#[cfg(feature = "exr")]
+use crate::exr;
+use crate::{bmp, hdr, jpg, pfm, png, ppm};
+use std::fs;
+use std::io;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, Sub};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Color {
@@ -7,6 +13,18 @@ pub struct Color {
     pub blue: f64,
 }
 
+// linear sRGB to CIE XYZ, D65 white point
+const SRGB_TO_XYZ: [[f64; 3]; 3] = [
+    [0.412_456_4, 0.357_576_1, 0.180_437_5],
+    [0.212_672_9, 0.715_152_2, 0.072_175_0],
+    [0.019_333_9, 0.119_192_0, 0.950_304_1],
+];
+const XYZ_TO_SRGB: [[f64; 3]; 3] = [
+    [3.240_454_2, -1.537_138_5, -0.498_531_4],
+    [-0.969_266_0, 1.876_010_8, 0.041_556_0],
+    [0.055_643_4, -0.204_025_9, 1.057_225_2],
+];
+
 fn clamp(val: f64, min: f64, max: f64) -> f64 {
     if val < min {
         min
@@ -27,6 +45,207 @@ impl Color {
         self.green = clamp(self.green, min, max);
         self.blue = clamp(self.blue, min, max);
     }
+
+    /// 8 bits red, green and blue of a color, clamped to [0, 1]
+    pub fn to_rgb8(self) -> [u8; 3] {
+        let numerize = |f: f64| (f.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [
+            numerize(self.red),
+            numerize(self.green),
+            numerize(self.blue),
+        ]
+    }
+
+    /// Color of 8 bits red, green and blue, in [0, 1]
+    pub fn from_rgb8(rgb: [u8; 3]) -> Color {
+        let normalize = |b: u8| b as f64 / 255.0;
+        Color::new(normalize(rgb[0]), normalize(rgb[1]), normalize(rgb[2]))
+    }
+
+    /// Linear interpolation from this color at `t` = 0 to `other` at 1,
+    /// `t` being clamped to [0, 1]
+    pub fn lerp(&self, other: &Color, t: f64) -> Color {
+        self.lerp_unclamped(other, t.clamp(0.0, 1.0))
+    }
+
+    /// Linear interpolation, extrapolating for `t` outside of [0, 1]
+    pub fn lerp_unclamped(&self, other: &Color, t: f64) -> Color {
+        (1.0 - t) * self + t * other
+    }
+
+    /// Channel-wise difference, negative channels being floored at zero
+    pub fn saturating_sub(&self, other: &Color) -> Color {
+        let diff = self - other;
+        Color::new(diff.red.max(0.0), diff.green.max(0.0), diff.blue.max(0.0))
+    }
+
+    /// Compress this linear color into the displayable range with `mode`
+    pub fn tone_map(&self, mode: ToneMap) -> Color {
+        mode.map(self)
+    }
+
+    /// Encode this linear color with the piecewise sRGB transfer function
+    pub fn to_srgb(self) -> Color {
+        let encode = |c: f64| {
+            if c <= 0.003_130_8 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        };
+        Color::new(encode(self.red), encode(self.green), encode(self.blue))
+    }
+
+    /// Linear color of this sRGB encoded one, undoing `to_srgb`
+    pub fn srgb_to_linear(&self) -> Color {
+        let decode = |c: f64| {
+            if c <= 0.040_45 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Color::new(decode(self.red), decode(self.green), decode(self.blue))
+    }
+
+    /// Linear color of an sRGB `#rrggbb` or `#rgb` hexadecimal code
+    pub fn from_hex(s: &str) -> Result<Color, String> {
+        let invalid = || format!("Invalid color {}, expected #rrggbb or #rgb", s);
+        let digits = s.strip_prefix('#').ok_or_else(invalid)?;
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let byte = |hex: &str| u8::from_str_radix(hex, 16).map_err(|_| invalid());
+        let rgb = match digits.len() {
+            6 => [
+                byte(&digits[0..2])?,
+                byte(&digits[2..4])?,
+                byte(&digits[4..6])?,
+            ],
+            // #abc is #aabbcc
+            3 => [
+                byte(&digits[0..1])? * 17,
+                byte(&digits[1..2])? * 17,
+                byte(&digits[2..3])? * 17,
+            ],
+            _ => return Err(invalid()),
+        };
+        Ok(Color::from_rgb8(rgb).srgb_to_linear())
+    }
+
+    /// Multiply the (red, green, blue) column vector by `matrix`
+    pub fn transform(&self, matrix: &[[f64; 3]; 3]) -> Color {
+        let row = |r: &[f64; 3]| r[0] * self.red + r[1] * self.green + r[2] * self.blue;
+        Color::new(row(&matrix[0]), row(&matrix[1]), row(&matrix[2]))
+    }
+
+    /// CIE XYZ coordinates of this linear sRGB color, Y is the luminance
+    pub fn to_xyz(self) -> (f64, f64, f64) {
+        let xyz = self.transform(&SRGB_TO_XYZ);
+        (xyz.red, xyz.green, xyz.blue)
+    }
+
+    /// Linear sRGB color of CIE XYZ coordinates, out of gamut colors have
+    /// negative components
+    pub fn from_xyz(x: f64, y: f64, z: f64) -> Color {
+        Color::new(x, y, z).transform(&XYZ_TO_SRGB)
+    }
+}
+
+/// Primaries of the linear output colors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSpace {
+    Srgb,
+    DisplayP3,
+}
+
+impl ColorSpace {
+    /// Conversion from the linear Rec.709/sRGB primaries used for rendering
+    pub fn matrix_from_srgb(&self) -> [[f64; 3]; 3] {
+        match self {
+            ColorSpace::Srgb => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            // both share the D65 white point, rows sum to 1
+            ColorSpace::DisplayP3 => [
+                [0.822_462_1, 0.177_538_0, 0.0],
+                [0.033_194_1, 0.966_805_9, 0.0],
+                [0.017_082_7, 0.072_397_4, 0.910_519_9],
+            ],
+        }
+    }
+}
+
+impl std::str::FromStr for ColorSpace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "srgb" => Ok(ColorSpace::Srgb),
+            "p3" => Ok(ColorSpace::DisplayP3),
+            _ => Err(format!("Unknown color space {}", s)),
+        }
+    }
+}
+
+/// Encoding of linear colors for display
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transfer {
+    /// Raise colors to this exponent, the reciprocal of the gamma
+    Gamma(f64),
+    Srgb,
+}
+
+impl Transfer {
+    pub fn encode(&self, color: &Color) -> Color {
+        match self {
+            Transfer::Gamma(exponent) => Color::new(
+                color.red.powf(*exponent),
+                color.green.powf(*exponent),
+                color.blue.powf(*exponent),
+            ),
+            Transfer::Srgb => color.to_srgb(),
+        }
+    }
+}
+
+/// Curve compressing linear colors into the displayable range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    /// Colors are cut at 1, highlights are blown out
+    Clamp,
+    /// c / (1 + c), only reaches 1 at infinity
+    Reinhard,
+    /// c (1 + c / white²) / (1 + c), reaches 1 at `white`
+    ReinhardExtended { white: f64 },
+    /// Narkowicz's fit of the ACES filmic curve, with a toe in the shadows
+    AcesFilmic,
+}
+
+impl ToneMap {
+    pub fn map(&self, color: &Color) -> Color {
+        let curve = |c: f64| match self {
+            ToneMap::Clamp => c.clamp(0.0, 1.0),
+            ToneMap::Reinhard => c / (1.0 + c),
+            ToneMap::ReinhardExtended { white } => c * (1.0 + c / (white * white)) / (1.0 + c),
+            ToneMap::AcesFilmic => {
+                let mapped = c * (2.51 * c + 0.03) / (c * (2.43 * c + 0.59) + 0.14);
+                mapped.clamp(0.0, 1.0)
+            }
+        };
+        Color::new(curve(color.red), curve(color.green), curve(color.blue))
+    }
+}
+
+impl std::str::FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(ToneMap::Clamp),
+            "reinhard" => Ok(ToneMap::Reinhard),
+            "aces" => Ok(ToneMap::AcesFilmic),
+            _ => Err(format!("Unknown tone map {}", s)),
+        }
+    }
 }
 
 pub mod colors {
@@ -41,6 +260,11 @@ pub mod colors {
         green: 1.0,
         blue: 1.0,
     };
+    pub const MAGENTA: Color = Color {
+        red: 1.0,
+        green: 0.0,
+        blue: 1.0,
+    };
 }
 
 impl std::default::Default for Color {
@@ -69,6 +293,34 @@ impl Add for Color {
     }
 }
 
+impl AddAssign for Color {
+    fn add_assign(&mut self, other: Color) {
+        self.red += other.red;
+        self.green += other.green;
+        self.blue += other.blue;
+    }
+}
+
+impl Sub for &Color {
+    type Output = Color;
+
+    fn sub(self, other: &Color) -> Color {
+        Color {
+            red: self.red - other.red,
+            green: self.green - other.green,
+            blue: self.blue - other.blue,
+        }
+    }
+}
+
+impl Sub for Color {
+    type Output = Color;
+
+    fn sub(self, other: Color) -> Color {
+        &self - &other
+    }
+}
+
 impl Mul<&Color> for f64 {
     type Output = Color;
 
@@ -89,6 +341,22 @@ impl Mul<Color> for f64 {
     }
 }
 
+impl Mul<f64> for &Color {
+    type Output = Color;
+
+    fn mul(self, val: f64) -> Color {
+        val * self
+    }
+}
+
+impl Mul<f64> for Color {
+    type Output = Color;
+
+    fn mul(self, val: f64) -> Color {
+        val * &self
+    }
+}
+
 impl Mul<Color> for Color {
     type Output = Color;
 
@@ -109,12 +377,55 @@ impl Div<f64> for &Color {
     }
 }
 
+impl DivAssign<f64> for Color {
+    fn div_assign(&mut self, val: f64) {
+        *self = &*self / val;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
 pub struct Image {
     pub width: usize,
     pub height: usize,
     pub data: Vec<Color>,
 }
 
+// extensions Image::save knows how to write
+fn is_supported_format(ext: &str) -> bool {
+    match ext {
+        "ppm" | "png" | "jpg" | "hdr" | "pfm" | "bmp" => true,
+        #[cfg(feature = "exr")]
+        "exr" => true,
+        _ => false,
+    }
+}
+
+/// Number of pixels of a `width` x `height` image, checked against
+/// `max_pixels` before anything gets allocated
+pub fn check_resolution(
+    width: usize,
+    height: usize,
+    max_pixels: usize,
+) -> Result<usize, RenderError> {
+    let pixels = width
+        .checked_mul(height)
+        .ok_or(RenderError::ResolutionOverflow { width, height })?;
+    if pixels > max_pixels {
+        return Err(RenderError::ResolutionTooLarge {
+            width,
+            height,
+            max: max_pixels,
+        });
+    }
+    Ok(pixels)
+}
+
 impl Image {
     pub fn new(width: usize, height: usize) -> Self {
         let sz: usize = width * height;
@@ -128,4 +439,528 @@ impl Image {
             data,
         }
     }
+
+    /// Build an image from separate channels, `None` if any of them
+    /// doesn't hold `width * height` values
+    pub fn from_channels(
+        red: &[f64],
+        green: &[f64],
+        blue: &[f64],
+        width: usize,
+        height: usize,
+    ) -> Option<Image> {
+        let sz = width * height;
+        if red.len() != sz || green.len() != sz || blue.len() != sz {
+            return None;
+        }
+        let data = red
+            .iter()
+            .zip(green)
+            .zip(blue)
+            .map(|((r, g), b)| Color::new(*r, *g, *b))
+            .collect();
+        Some(Image {
+            width,
+            height,
+            data,
+        })
+    }
+
+    pub fn channel(&self, c: Channel) -> Vec<f64> {
+        self.data
+            .iter()
+            .map(|px| match c {
+                Channel::Red => px.red,
+                Channel::Green => px.green,
+                Channel::Blue => px.blue,
+            })
+            .collect()
+    }
+
+    /// Flag the pixels having any channel above `max`
+    pub fn clipped_mask(&self, max: f64) -> Vec<bool> {
+        self.data
+            .iter()
+            .map(|px| px.red > max || px.green > max || px.blue > max)
+            .collect()
+    }
+
+    /// Per channel (red, green, blue) histograms of the displayed range [0, 1],
+    /// split in at least one bin
+    pub fn histogram(&self, bins: usize) -> [Vec<u32>; 3] {
+        assert!(bins > 0, "Histograms need at least one bin");
+        let mut hist = [vec![0; bins], vec![0; bins], vec![0; bins]];
+        let bin = |val: f64| ((clamp(val, 0.0, 1.0) * bins as f64) as usize).min(bins - 1);
+        for px in &self.data {
+            hist[0][bin(px.red)] += 1;
+            hist[1][bin(px.green)] += 1;
+            hist[2][bin(px.blue)] += 1;
+        }
+        hist
+    }
+
+    /// Stretch each channel so that its `low` percentile maps to 0 and its
+    /// `high` percentile maps to 1, percentiles being given in [0, 1].
+    /// NaN and infinite values are left out of the percentiles.
+    pub fn auto_levels(&mut self, low: f64, high: f64) {
+        assert!(
+            (0.0..high).contains(&low) && high <= 1.0,
+            "Auto levels percentiles must satisfy 0 <= low < high <= 1, got {} and {}",
+            low,
+            high
+        );
+        let channels: [fn(&mut Color) -> &mut f64; 3] =
+            [|c| &mut c.red, |c| &mut c.green, |c| &mut c.blue];
+        for channel in channels.iter() {
+            let mut values: Vec<f64> = self
+                .data
+                .iter_mut()
+                .map(|c| *channel(c))
+                .filter(|v| v.is_finite())
+                .collect();
+            if values.is_empty() {
+                continue;
+            }
+            values.sort_by(f64::total_cmp);
+            let last = (values.len() - 1) as f64;
+            let min = values[(low * last).round() as usize];
+            let max = values[(high * last).round() as usize];
+            if max <= min {
+                continue;
+            }
+            for px in self.data.iter_mut() {
+                let val = channel(px);
+                *val = clamp((*val - min) / (max - min), 0.0, 1.0);
+            }
+        }
+    }
+
+    /// Write the image to `path` in the format given by its extension
+    pub fn save(&self, path: &str) -> Result<(), RenderError> {
+        let ext = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        // check the format before creating the file
+        if !is_supported_format(&ext) {
+            return Err(RenderError::UnsupportedFormat(ext));
+        }
+        if ext == "png" {
+            return png::write_png(path, self);
+        }
+        let file = fs::File::create(path)?;
+        match ext.as_str() {
+            "jpg" => return jpg::write_jpg(io::BufWriter::new(file), self),
+            "hdr" => return hdr::write_hdr(io::BufWriter::new(file), self),
+            "ppm" => ppm::PPMWriter::binary(io::BufWriter::new(file)).write(self),
+            "bmp" => bmp::write_bmp(file, self),
+            #[cfg(feature = "exr")]
+            "exr" => exr::write_exr(io::BufWriter::new(file), self),
+            _ => pfm::write_pfm(file, self),
+        }?;
+        Ok(())
+    }
+
+    /// Enlarge the image by `factor`, edges are handled by clamping
+    pub fn upscale(&self, factor: usize, kernel: UpscaleKernel) -> Image {
+        assert!(factor >= 1, "Upscale factor must be at least 1");
+        let mut out = Image::new(self.width * factor, self.height * factor);
+        for line in 0..out.height {
+            let rows = kernel.taps(line, factor, self.height);
+            for col in 0..out.width {
+                let cols = kernel.taps(col, factor, self.width);
+                let mut color = colors::BLACK;
+                for (y, wy) in &rows {
+                    for (x, wx) in &cols {
+                        color += (wy * wx) * &self.data[y * self.width + x];
+                    }
+                }
+                out.data[line * out.width + col] = color;
+            }
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpscaleKernel {
+    Bilinear,
+    Lanczos,
+}
+
+impl std::str::FromStr for UpscaleKernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bilinear" => Ok(UpscaleKernel::Bilinear),
+            "lanczos" => Ok(UpscaleKernel::Lanczos),
+            _ => Err(format!("Unknown upscale kernel {}", s)),
+        }
+    }
+}
+
+impl UpscaleKernel {
+    // lobes of the Lanczos window
+    const LANCZOS_A: i64 = 3;
+
+    fn weight(&self, x: f64) -> f64 {
+        match self {
+            UpscaleKernel::Bilinear => (1.0 - x.abs()).max(0.0),
+            UpscaleKernel::Lanczos => {
+                let a = Self::LANCZOS_A as f64;
+                if x == 0.0 {
+                    1.0
+                } else if x.abs() < a {
+                    let px = std::f64::consts::PI * x;
+                    a * px.sin() * (px / a).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    // source indices and normalized weights contributing to output `pos`
+    fn taps(&self, pos: usize, factor: usize, len: usize) -> Vec<(usize, f64)> {
+        // output pixel centers mapped back into source pixel coordinates
+        let src = (pos as f64 + 0.5) / factor as f64 - 0.5;
+        let radius = match self {
+            UpscaleKernel::Bilinear => 1,
+            UpscaleKernel::Lanczos => Self::LANCZOS_A,
+        };
+        let start = src.floor() as i64 - radius + 1;
+        let mut taps: Vec<(usize, f64)> = (start..start + 2 * radius)
+            .map(|i| {
+                let idx = i.max(0).min(len as i64 - 1) as usize;
+                (idx, self.weight(src - i as f64))
+            })
+            .collect();
+        let total: f64 = taps.iter().map(|(_, w)| w).sum();
+        for tap in taps.iter_mut() {
+            tap.1 /= total;
+        }
+        taps
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn srgb_reference_values() {
+        let encoded = |c: f64| Color::new(c, c, c).to_srgb().red;
+        assert_eq!(0.0, encoded(0.0));
+        assert!((encoded(0.5) - 0.735_356_983_052_871).abs() < 1e-9);
+        assert!((encoded(1.0) - 1.0).abs() < 1e-12);
+        // linear segment near black
+        assert!((encoded(0.002) - 0.025_84).abs() < 1e-12);
+        let gamma = Transfer::Gamma(0.5).encode(&Color::new(0.25, 0.25, 0.25));
+        assert_eq!(0.5, gamma.red);
+    }
+    #[test]
+    fn color_lerp() {
+        let black = colors::BLACK;
+        let c = Color::new(1.0, 0.5, 0.25);
+        let rgb = |c: Color| (c.red, c.green, c.blue);
+        assert_eq!((0.0, 0.0, 0.0), rgb(black.lerp(&c, 0.0)));
+        assert_eq!((1.0, 0.5, 0.25), rgb(black.lerp(&c, 1.0)));
+        assert_eq!((0.5, 0.25, 0.125), rgb(black.lerp(&c, 0.5)));
+        assert_eq!((0.0, 0.0, 0.0), rgb(black.lerp(&c, -1.0)));
+        assert_eq!((-1.0, -0.5, -0.25), rgb(black.lerp_unclamped(&c, -1.0)));
+    }
+    #[test]
+    fn hex_colors() {
+        let rgb = |c: Color| (c.red, c.green, c.blue);
+        assert_eq!((1.0, 1.0, 1.0), rgb(Color::from_hex("#ffffff").unwrap()));
+        assert_eq!((0.0, 0.0, 0.0), rgb(Color::from_hex("#000000").unwrap()));
+        let orange = Color::from_hex("#E08a1F").unwrap();
+        // mid sRGB values are much darker once linear
+        assert!((orange.green - 0.254_152).abs() < 1e-6);
+        assert_eq!([0xe0, 0x8a, 0x1f], orange.to_srgb().to_rgb8());
+        let short = Color::from_hex("#f80").unwrap();
+        assert_eq!([0xff, 0x88, 0x00], short.to_srgb().to_rgb8());
+        for bad in &["ffffff", "#fffff", "#ggg", "#+1+1+1", "#ffé"] {
+            assert!(Color::from_hex(bad).is_err(), "{} parsed", bad);
+        }
+    }
+    #[test]
+    fn color_subtraction() {
+        let a = Color::new(0.75, 0.5, 0.25);
+        let b = Color::new(0.25, 0.75, 0.25);
+        let rgb = |c: Color| (c.red, c.green, c.blue);
+        assert_eq!((0.5, -0.25, 0.0), rgb(a - b));
+        assert_eq!((0.5, -0.25, 0.0), rgb(&a - &b));
+        assert_eq!((0.5, 0.0, 0.0), rgb(a.saturating_sub(&b)));
+        assert_eq!((0.0, 0.25, 0.0), rgb(b.saturating_sub(&a)));
+        assert_eq!((1.5, 1.0, 0.5), rgb(a * 2.0));
+        assert_eq!(rgb(2.0 * a), rgb(&a * 2.0));
+    }
+    #[test]
+    fn compound_assignment_on_colors() {
+        let mut c = Color::new(0.5, 1.0, 0.25);
+        c += Color::new(0.5, 1.0, 0.25);
+        assert_eq!((1.0, 2.0, 0.5), (c.red, c.green, c.blue));
+        c /= 2.0;
+        assert_eq!((0.5, 1.0, 0.25), (c.red, c.green, c.blue));
+    }
+    #[test]
+    fn rgb8_is_clamped_and_rounded() {
+        let rgb8 = |f: f64| Color::new(f, f, f).to_rgb8()[0];
+        assert_eq!(255, rgb8(1.0));
+        assert_eq!(255, rgb8(1.5));
+        assert_eq!(0, rgb8(-0.1));
+        assert_eq!(128, rgb8(0.5));
+        assert_eq!(0, rgb8(f64::NAN));
+    }
+    #[test]
+    fn auto_levels_stretches_low_contrast() {
+        let mut img = Image::new(2, 2);
+        for (px, val) in img.data.iter_mut().zip(&[0.3, 0.35, 0.45, 0.5]) {
+            *px = Color::new(*val, *val, *val);
+        }
+        let before = img.histogram(10);
+        assert_eq!(0, before[0][0]);
+        assert_eq!(0, before[0][9]);
+        img.auto_levels(0.0, 1.0);
+        let (min, max) = img.data.iter().fold((1.0f64, 0.0f64), |(min, max), c| {
+            (min.min(c.red), max.max(c.red))
+        });
+        assert_eq!(0.0, min);
+        assert_eq!(1.0, max);
+        let after = img.histogram(10);
+        for channel in after.iter() {
+            assert_eq!(1, channel[0]);
+            assert_eq!(1, channel[9]);
+            assert_eq!(4, channel.iter().sum::<u32>());
+        }
+    }
+    #[test]
+    fn auto_levels_skip_non_finite_values() {
+        let mut img = Image::new(2, 2);
+        for (px, val) in img
+            .data
+            .iter_mut()
+            .zip(&[0.3, f64::NAN, 0.5, f64::INFINITY])
+        {
+            *px = Color::new(*val, 0.4, *val);
+        }
+        img.auto_levels(0.0, 1.0);
+        assert_eq!((0.0, 1.0), (img.data[0].red, img.data[2].red));
+        assert_eq!(1.0, img.data[3].blue);
+        // a flat channel is left as is
+        assert_eq!(0.4, img.data[1].green);
+    }
+    #[test]
+    #[should_panic(expected = "Auto levels percentiles must satisfy 0 <= low < high <= 1")]
+    fn auto_levels_with_reversed_percentiles() {
+        Image::new(2, 2).auto_levels(0.9, 0.1);
+    }
+    #[test]
+    #[should_panic(expected = "Histograms need at least one bin")]
+    fn histogram_without_bins() {
+        Image::new(1, 1).histogram(0);
+    }
+    #[test]
+    fn save_dispatches_on_extension() {
+        let mut img = Image::new(2, 2);
+        img.data[1] = Color::new(0.5, 0.25, 0.75);
+        let dir = std::env::temp_dir();
+        let path = |name: &str| {
+            let name = format!("raytracer-{}-{}", std::process::id(), name);
+            dir.join(name).to_str().unwrap().to_string()
+        };
+        let unknown = path("out.xyz");
+        assert_eq!(
+            Err(RenderError::UnsupportedFormat("xyz".to_string())),
+            img.save(&unknown)
+        );
+        assert!(!std::path::Path::new(&unknown).exists());
+        let ppm = path("out.ppm");
+        img.save(&ppm).unwrap();
+        let data = fs::read(&ppm).unwrap();
+        assert_eq!(b"P6\n2 2\n255\n\0\0\0\x80\x40\xbf", &data[..17]);
+        let png = path("out.PNG");
+        img.save(&png).unwrap();
+        let data = fs::read(&png).unwrap();
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &data[..8]);
+        assert_eq!(b"IHDR", &data[12..16]);
+        let jpg = path("out.jpg");
+        img.save(&jpg).unwrap();
+        let data = fs::read(&jpg).unwrap();
+        // start of image marker
+        assert_eq!(b"\xff\xd8", &data[..2]);
+        let hdr = path("out.hdr");
+        img.save(&hdr).unwrap();
+        let data = fs::read(&hdr).unwrap();
+        assert!(data.starts_with(b"#?RADIANCE\n"));
+        for file in &[ppm, png, jpg, hdr] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+    #[test]
+    fn resolution_above_cap_is_rejected() {
+        assert_eq!(Ok(1200 * 800), check_resolution(1200, 800, 1 << 20));
+        assert_eq!(
+            Err(RenderError::ResolutionTooLarge {
+                width: 2048,
+                height: 1024,
+                max: 1 << 20
+            }),
+            check_resolution(2048, 1024, 1 << 20)
+        );
+        assert_eq!(
+            Err(RenderError::ResolutionOverflow {
+                width: usize::MAX / 2,
+                height: 3
+            }),
+            check_resolution(usize::MAX / 2, 3, usize::MAX)
+        );
+    }
+    #[test]
+    fn extended_reinhard_reaches_white() {
+        let extended = ToneMap::ReinhardExtended { white: 4.0 };
+        let at_white = extended.map(&Color::new(4.0, 4.0, 4.0));
+        assert!((at_white.red - 1.0).abs() < 1e-12);
+        assert!(extended.map(&Color::new(8.0, 8.0, 8.0)).red >= 1.0);
+        // highlights are kept brighter than with plain Reinhard
+        let high = Color::new(3.0, 3.0, 3.0);
+        assert!(extended.map(&high).red > ToneMap::Reinhard.map(&high).red + 0.1);
+        // dark values are mapped the same way
+        let low = Color::new(0.01, 0.01, 0.01);
+        let diff = extended.map(&low).red - ToneMap::Reinhard.map(&low).red;
+        assert!(diff.abs() < 1e-3 * low.red);
+    }
+    #[test]
+    fn reinhard_approaches_white() {
+        let reinhard = |c: f64| Color::new(c, c, c).tone_map(ToneMap::Reinhard).red;
+        assert_eq!(0.0, reinhard(0.0));
+        assert_eq!(0.5, reinhard(1.0));
+        assert!(reinhard(1e6) < 1.0);
+        assert!(reinhard(1e6) > 0.999);
+    }
+    #[test]
+    fn aces_stays_displayable() {
+        for &c in &[0.0, 1e-3, 0.18, 0.5, 1.0, 4.0, 100.0, 1e9] {
+            let mapped = Color::new(c, c, c).tone_map(ToneMap::AcesFilmic).red;
+            assert!((0.0..=1.0).contains(&mapped), "{} mapped to {}", c, mapped);
+        }
+        assert_eq!(0.0, ToneMap::AcesFilmic.map(&colors::BLACK).red);
+        assert_eq!(1.0, ToneMap::AcesFilmic.map(&Color::new(1e9, 1e9, 1e9)).red);
+        assert_eq!(1.0, ToneMap::Clamp.map(&Color::new(3.0, 3.0, 3.0)).red);
+        assert_eq!(Ok(ToneMap::AcesFilmic), "aces".parse());
+        assert!("filmic".parse::<ToneMap>().is_err());
+    }
+    #[test]
+    fn white_converts_to_d65() {
+        let (x, y, z) = colors::WHITE.to_xyz();
+        assert!((x - 0.950_47).abs() < 1e-5);
+        assert!((y - 1.0).abs() < 1e-5);
+        assert!((z - 1.088_83).abs() < 1e-5);
+        let white = Color::from_xyz(x, y, z);
+        assert!((white.red - 1.0).abs() < 1e-6);
+        assert!((white.green - 1.0).abs() < 1e-6);
+        assert!((white.blue - 1.0).abs() < 1e-6);
+        let color = Color::new(0.2, 0.5, 0.9);
+        let (x, y, z) = color.to_xyz();
+        let back = Color::from_xyz(x, y, z);
+        assert!((back.red - 0.2).abs() < 1e-6);
+        assert!((back.green - 0.5).abs() < 1e-6);
+        assert!((back.blue - 0.9).abs() < 1e-6);
+    }
+    #[test]
+    fn clipped_mask_flags_any_channel() {
+        let mut img = Image::new(3, 1);
+        img.data[0] = Color::new(0.2, 1.5, 0.1);
+        img.data[1] = Color::new(1.0, 1.0, 1.0);
+        img.data[2] = Color::new(0.0, 0.0, 3.0);
+        assert_eq!(vec![true, false, true], img.clipped_mask(1.0));
+        assert_eq!(vec![true, true, true], img.clipped_mask(0.5));
+    }
+    #[test]
+    fn bilinear_upscale_interpolates() {
+        let mut img = Image::new(2, 2);
+        for (px, val) in img.data.iter_mut().zip(&[0.0, 1.0, 1.0, 0.0]) {
+            *px = Color::new(*val, *val, *val);
+        }
+        let big = img.upscale(2, UpscaleKernel::Bilinear);
+        assert_eq!(4, big.width);
+        assert_eq!(4, big.height);
+        let expected = [
+            [0.0, 0.25, 0.75, 1.0],
+            [0.25, 0.375, 0.625, 0.75],
+            [0.75, 0.625, 0.375, 0.25],
+            [1.0, 0.75, 0.25, 0.0],
+        ];
+        for (row, pixels) in expected.iter().zip(big.data.chunks(4)) {
+            for (val, px) in row.iter().zip(pixels) {
+                assert!((val - px.red).abs() < 1e-12);
+            }
+        }
+    }
+    #[test]
+    #[should_panic(expected = "Upscale factor must be at least 1")]
+    fn upscale_by_zero() {
+        Image::new(2, 2).upscale(0, UpscaleKernel::Bilinear);
+    }
+    #[test]
+    fn lanczos_keeps_edges_sharper() {
+        let mut img = Image::new(8, 1);
+        for (col, px) in img.data.iter_mut().enumerate() {
+            let val = if col < 4 { 0.0 } else { 1.0 };
+            *px = Color::new(val, val, val);
+        }
+        let max_slope = |img: &Image| {
+            img.data
+                .windows(2)
+                .map(|w| (w[1].red - w[0].red).abs())
+                .fold(0.0, f64::max)
+        };
+        let bilinear = img.upscale(4, UpscaleKernel::Bilinear);
+        let lanczos = img.upscale(4, UpscaleKernel::Lanczos);
+        assert!(max_slope(&lanczos) > max_slope(&bilinear));
+    }
+    #[test]
+    fn channels_recombine_unchanged() {
+        let mut img = Image::new(3, 2);
+        for (i, px) in img.data.iter_mut().enumerate() {
+            let i = i as f64;
+            *px = Color::new(i / 10.0, i / 20.0, 1.0 - i / 10.0);
+        }
+        let red = img.channel(Channel::Red);
+        assert_eq!(vec![0.0, 0.1, 0.2, 0.3, 0.4, 0.5], red);
+        let green = img.channel(Channel::Green);
+        let blue = img.channel(Channel::Blue);
+        let back = Image::from_channels(&red, &green, &blue, 3, 2).unwrap();
+        assert_eq!(3, back.width);
+        assert_eq!(2, back.height);
+        for (a, b) in img.data.iter().zip(&back.data) {
+            assert_eq!(a.red, b.red);
+            assert_eq!(a.green, b.green);
+            assert_eq!(a.blue, b.blue);
+        }
+    }
+    #[test]
+    fn mismatched_channels_are_rejected() {
+        let full = vec![0.0; 6];
+        let short = vec![0.0; 5];
+        assert!(Image::from_channels(&full, &short, &full, 3, 2).is_none());
+        assert!(Image::from_channels(&full, &full, &full, 2, 2).is_none());
+    }
+    #[test]
+    fn p3_keeps_neutrals_and_desaturates_srgb_red() {
+        let matrix = ColorSpace::DisplayP3.matrix_from_srgb();
+        let gray = Color::new(0.5, 0.5, 0.5).transform(&matrix);
+        assert!((gray.red - 0.5).abs() < 1e-6);
+        assert!((gray.green - 0.5).abs() < 1e-6);
+        assert!((gray.blue - 0.5).abs() < 1e-6);
+        // sRGB red lies inside the wider P3 gamut
+        let red = Color::new(1.0, 0.0, 0.0).transform(&matrix);
+        assert!((red.red - 0.822_462_1).abs() < 1e-6);
+        assert!((red.green - 0.033_194_1).abs() < 1e-6);
+        assert!((red.blue - 0.017_082_7).abs() < 1e-6);
+        let same = Color::new(0.2, 0.4, 0.6).transform(&ColorSpace::Srgb.matrix_from_srgb());
+        assert_eq!((0.2, 0.4, 0.6), (same.red, same.green, same.blue));
+    }
 }
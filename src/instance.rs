@@ -0,0 +1,199 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::ray::{HitRecord, Hittable, Ray};
+use crate::vec::{Point, Vector};
+
+// `bbox` scaled by a positive `factor`, bounds keep their order
+fn scale_box(bbox: Aabb, factor: f64) -> Aabb {
+    let scale = |i: Interval| Interval::new(i.min * factor, i.max * factor);
+    Aabb::new(scale(bbox.x), scale(bbox.y), scale(bbox.z))
+}
+
+/// Uniform scaling of an object around the origin
+pub struct Scale<T: Hittable> {
+    pub object: T,
+    pub factor: f64,
+}
+
+impl<T: Hittable> Scale<T> {
+    pub fn new(object: T, factor: f64) -> Self {
+        Scale { object, factor }
+    }
+}
+
+impl<T: Hittable> Hittable for Scale<T> {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        // scaling both origin and direction keeps the ray parameter unchanged,
+        // normals are not affected by a uniform scale
+        let local = Ray::timed(
+            ray.origin / self.factor,
+            ray.direction / self.factor,
+            ray.time,
+        );
+        self.object.hit(&local, t_range).map(|hit| HitRecord {
+            point: hit.point * self.factor,
+            ..hit
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        scale_box(self.object.bounding_box(), self.factor)
+    }
+
+    fn bounded_box(&self) -> Aabb {
+        scale_box(self.object.bounded_box(), self.factor)
+    }
+}
+
+/// Object moved by `offset`
+pub struct Translate<T: Hittable> {
+    pub object: T,
+    pub offset: Vector,
+}
+
+impl<T: Hittable> Translate<T> {
+    pub fn new(object: T, offset: Vector) -> Self {
+        Translate { object, offset }
+    }
+}
+
+impl<T: Hittable> Hittable for Translate<T> {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        let local = Ray::timed(ray.origin - self.offset, ray.direction, ray.time);
+        self.object.hit(&local, t_range).map(|hit| HitRecord {
+            point: hit.point + self.offset,
+            ..hit
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let bbox = self.object.bounding_box();
+        let shift = |i: Interval, by: f64| Interval::new(i.min + by, i.max + by);
+        Aabb::new(
+            shift(bbox.x, self.offset.x),
+            shift(bbox.y, self.offset.y),
+            shift(bbox.z, self.offset.z),
+        )
+    }
+}
+
+/// Object turned around the y axis, counterclockwise seen from above
+pub struct RotateY<T: Hittable> {
+    pub object: T,
+    pub sin_theta: f64,
+    pub cos_theta: f64,
+}
+
+impl<T: Hittable> RotateY<T> {
+    pub fn new(object: T, degrees: f64) -> Self {
+        let theta = degrees.to_radians();
+        RotateY {
+            object,
+            sin_theta: theta.sin(),
+            cos_theta: theta.cos(),
+        }
+    }
+
+    // from the object to the world
+    fn rotate(&self, v: &Vector) -> Vector {
+        Vector::new(
+            self.cos_theta * v.x + self.sin_theta * v.z,
+            v.y,
+            -self.sin_theta * v.x + self.cos_theta * v.z,
+        )
+    }
+
+    // from the world to the object
+    fn unrotate(&self, v: &Vector) -> Vector {
+        Vector::new(
+            self.cos_theta * v.x - self.sin_theta * v.z,
+            v.y,
+            self.sin_theta * v.x + self.cos_theta * v.z,
+        )
+    }
+}
+
+impl<T: Hittable> Hittable for RotateY<T> {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        // a rotation keeps the ray parameter unchanged
+        let local = Ray::timed(
+            self.unrotate(&ray.origin),
+            self.unrotate(&ray.direction),
+            ray.time,
+        );
+        self.object.hit(&local, t_range).map(|hit| HitRecord {
+            point: self.rotate(&hit.point),
+            normal: self.rotate(&hit.normal),
+            ..hit
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // box around the turned corners of the object's box
+        let bbox = self.object.bounding_box();
+        let mut rotated = Aabb::EMPTY;
+        for &x in &[bbox.x.min, bbox.x.max] {
+            for &z in &[bbox.z.min, bbox.z.max] {
+                let corner = self.rotate(&Point::new(x, 0.0, z));
+                let low = Point::new(corner.x, bbox.y.min, corner.z);
+                let high = Point::new(corner.x, bbox.y.max, corner.z);
+                rotated = Aabb::surrounding(&rotated, &Aabb::from_points(&low, &high));
+            }
+        }
+        rotated
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::test::gray;
+    use crate::sphere::Sphere;
+    #[test]
+    fn scaled_sphere_hit() {
+        let sphere = Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, gray());
+        let scaled = Scale::new(sphere, 10.0);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = scaled.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-9);
+        assert!(hit.point.approx_eq(&Point::new(0.0, 0.0, -5.0), 1e-9));
+        assert!(hit.normal.approx_eq(&Vector::new(0.0, 0.0, 1.0), 1e-9));
+    }
+
+    fn ball(center: Point) -> Sphere {
+        Sphere::new(center, 0.5, gray())
+    }
+
+    #[test]
+    fn translated_sphere_hit() {
+        let moved = Translate::new(ball(Point::new(0.0, 0.0, 0.0)), Vector::new(3.0, 1.0, -2.0));
+        let ray = Ray::new(Point::new(3.0, 1.0, 2.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = moved.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert_eq!(3.5, hit.t);
+        assert_eq!(Point::new(3.0, 1.0, -1.5), hit.point);
+        assert_eq!(Vector::new(0.0, 0.0, 1.0), hit.normal);
+        let bbox = moved.bounding_box();
+        assert_eq!(Point::new(2.5, 0.5, -2.5), bbox.min());
+        assert_eq!(Point::new(3.5, 1.5, -1.5), bbox.max());
+        let origin = Ray::new(Point::new(0.0, 0.0, 2.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(moved.hit(&origin, Interval::new(0.001, f64::MAX)).is_none());
+    }
+
+    #[test]
+    fn quarter_turn_maps_x_to_z() {
+        // the sphere on the x axis ends up on -z
+        let turned = RotateY::new(ball(Point::new(2.0, 0.0, 0.0)), 90.0);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = turned.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!((hit.t - 1.5).abs() < 1e-9);
+        assert!(hit.point.approx_eq(&Point::new(0.0, 0.0, -1.5), 1e-9));
+        assert!(hit.normal.approx_eq(&Vector::new(0.0, 0.0, 1.0), 1e-9));
+        let along_x = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(turned
+            .hit(&along_x, Interval::new(0.001, f64::MAX))
+            .is_none());
+        let bbox = turned.bounding_box();
+        assert!(bbox.min().approx_eq(&Point::new(-0.5, -0.5, -2.5), 1e-9));
+        assert!(bbox.max().approx_eq(&Point::new(0.5, 0.5, -1.5), 1e-9));
+    }
+}
@@ -0,0 +1,99 @@
+/// Closed range of real numbers [min, max], empty when min > max
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Interval {
+    pub const EMPTY: Interval = Interval {
+        min: f64::INFINITY,
+        max: f64::NEG_INFINITY,
+    };
+    pub const UNIVERSE: Interval = Interval {
+        min: f64::NEG_INFINITY,
+        max: f64::INFINITY,
+    };
+
+    pub fn new(min: f64, max: f64) -> Self {
+        Self { min, max }
+    }
+
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    /// `x` is within the interval, bounds included
+    pub fn contains(&self, x: f64) -> bool {
+        (self.min..=self.max).contains(&x)
+    }
+
+    /// `x` is strictly within the interval, bounds excluded
+    pub fn surrounds(&self, x: f64) -> bool {
+        self.min < x && x < self.max
+    }
+
+    pub fn clamp(&self, x: f64) -> f64 {
+        if x < self.min {
+            self.min
+        } else if x > self.max {
+            self.max
+        } else {
+            x
+        }
+    }
+
+    /// Both intervals have at least one value in common
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        self.min.max(other.min) <= self.max.min(other.max)
+    }
+
+    /// Interval grown by `delta`, half on each side
+    pub fn expand(&self, delta: f64) -> Interval {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn contains_includes_bounds() {
+        let i = Interval::new(1.0, 2.0);
+        assert!(i.contains(1.0));
+        assert!(i.contains(2.0));
+        assert!(i.contains(1.5));
+        assert!(!i.contains(0.999));
+        assert!(!i.contains(2.001));
+    }
+    #[test]
+    fn surrounds_excludes_bounds() {
+        let i = Interval::new(1.0, 2.0);
+        assert!(!i.surrounds(1.0));
+        assert!(!i.surrounds(2.0));
+        assert!(i.surrounds(1.5));
+    }
+    #[test]
+    fn empty_and_universe() {
+        assert!(!Interval::EMPTY.contains(0.0));
+        assert!(Interval::UNIVERSE.surrounds(f64::MAX));
+        assert!(Interval::UNIVERSE.surrounds(f64::MIN));
+    }
+    #[test]
+    fn overlapping_intervals() {
+        let i = Interval::new(0.0, 1.0);
+        assert!(i.overlaps(&Interval::new(0.5, 2.0)));
+        assert!(i.overlaps(&Interval::new(1.0, 2.0)));
+        assert!(!i.overlaps(&Interval::new(1.5, 2.0)));
+        assert!(!i.overlaps(&Interval::EMPTY));
+    }
+    #[test]
+    fn clamp_and_expand() {
+        let i = Interval::new(-1.0, 1.0);
+        assert_eq!(-1.0, i.clamp(-3.0));
+        assert_eq!(1.0, i.clamp(3.0));
+        assert_eq!(0.5, i.clamp(0.5));
+        assert_eq!(Interval::new(-1.5, 1.5), i.expand(1.0));
+    }
+}
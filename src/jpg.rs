@@ -0,0 +1,50 @@
+use crate::error::RenderError;
+use crate::image::Image;
+use ::image::codecs::jpeg::JpegEncoder;
+use ::image::ColorType;
+use std::io;
+
+// high enough for the compression blocks not to show on smooth gradients
+const QUALITY: u8 = 90;
+
+/// Write `img` as an 8 bits RGB JPEG, colors are expected in [0, 1)
+pub fn write_jpg<W: io::Write>(mut writer: W, img: &Image) -> Result<(), RenderError> {
+    let mut rgb8 = Vec::with_capacity(3 * img.data.len());
+    for px in &img.data {
+        rgb8.extend_from_slice(&px.to_rgb8());
+    }
+    JpegEncoder::new_with_quality(&mut writer, QUALITY)
+        .encode(&rgb8, img.width as u32, img.height as u32, ColorType::Rgb8)
+        .map_err(|e| RenderError::Io(e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+
+    #[test]
+    fn flat_colors_decode_with_image_crate() {
+        // 8x8 blocks of a single color survive the compression almost intact
+        let mut img = Image::new(16, 8);
+        for (index, px) in img.data.iter_mut().enumerate() {
+            *px = if index % 16 < 8 {
+                Color::new(0.8, 0.2, 0.1)
+            } else {
+                Color::new(0.1, 0.3, 0.9)
+            };
+        }
+        let mut out = Vec::new();
+        write_jpg(&mut out, &img).unwrap();
+        let decoded = ::image::load_from_memory(&out).unwrap().to_rgb8();
+        assert_eq!((16, 8), decoded.dimensions());
+        let raw = decoded.into_raw();
+        let close = |px: &[u8], expected: [u8; 3]| {
+            px.iter()
+                .zip(&expected)
+                .all(|(&a, &b)| (a as i32 - b as i32).abs() <= 8)
+        };
+        assert!(close(&raw[3 * 17..3 * 18], [204, 51, 26]));
+        assert!(close(&raw[3 * 30..3 * 31], [26, 77, 230]));
+    }
+}
@@ -0,0 +1,19 @@
+use crate::image::Color;
+use crate::vec::Point;
+
+/// Light emitted from a single point, evenly in every direction
+#[derive(Debug, Clone, Copy)]
+pub struct PointLight {
+    pub position: Point,
+    /// Light received one unit away, it falls off with the squared distance
+    pub intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Point, intensity: Color) -> Self {
+        PointLight {
+            position,
+            intensity,
+        }
+    }
+}
@@ -1,15 +1,52 @@
-use rand::{self, distributions::Distribution, Rng};
-use std::fs;
-use std::io::{self, Write};
+use rand::rngs::StdRng;
+use rand::{self, Rng, SeedableRng};
+use rayon::prelude::*;
+use std::sync::Mutex;
+use std::time::Duration;
 use structopt::StructOpt;
+mod aabb;
+mod background;
+mod bmp;
+mod box_prim;
+mod capsule;
+mod checkpoint;
+mod cylinder;
+mod denoise;
+mod disk;
+mod error;
+#[cfg(feature = "exr")]
+mod exr;
+mod hdr;
 mod image;
+mod instance;
+mod interval;
+mod jpg;
+mod light;
 mod material;
+mod medium;
+mod obj;
+mod pfm;
+mod plane;
+mod png;
 mod ppm;
+mod preview;
+mod progress;
 mod ray;
+mod refine;
+mod rng;
+mod sampler;
+mod scene;
 mod sphere;
+mod stats;
+mod texture;
+mod triangle;
 mod vec;
-use image::Color;
-use ray::{HittableVec, Ray};
+use crate::background::{Background, GradientSky};
+use crate::image::Color;
+use instance::Scale;
+use interval::Interval;
+use material::{BounceCategory, Material};
+use ray::{Hittable, HittableVec, Ray, World};
 use sphere::Sphere;
 use vec::{Point, Vector};
 
@@ -18,6 +55,181 @@ use vec::{Point, Vector};
 struct Options {
     #[structopt(short, long, default_value = "1200")]
     width: u16,
+    /// Image height, defaults to a 3:2 aspect ratio
+    #[structopt(long)]
+    height: Option<u16>,
+    /// Refuse to render images with more pixels than this
+    #[structopt(long, default_value = "67108864")]
+    max_resolution: usize,
+    /// Number of samples per pixel, 100 by default
+    #[structopt(long)]
+    samples: Option<u16>,
+    /// Total number of camera rays, sets the samples per pixel instead of --samples
+    #[structopt(long, conflicts_with = "samples")]
+    ray_budget: Option<u64>,
+    /// Placement of the samples in each pixel: random, stratified, halton or sobol
+    #[structopt(long, default_value = "random")]
+    sampler: sampler::Sampler,
+    /// Number of samples rendered and discarded per pixel before accumulating
+    #[structopt(long, default_value = "0")]
+    warmup: u16,
+    /// Maximum number of bounces on dielectric surfaces along a path, 50 by default
+    #[structopt(long)]
+    glass_bounces: Option<u16>,
+    /// Maximum number of bounces on diffuse and metallic surfaces along a path,
+    /// 50 by default
+    #[structopt(long)]
+    diffuse_bounces: Option<u16>,
+    /// Maximum number of bounces of each kind, instead of --glass-bounces and --diffuse-bounces
+    #[structopt(long, conflicts_with_all = &["glass-bounces", "diffuse-bounces"])]
+    bounces: Option<u16>,
+    /// Replace every material: lambertian-gray, normal or mirror
+    #[structopt(long)]
+    material_override: Option<MaterialOverride>,
+    /// Primaries of the output image: srgb or p3
+    #[structopt(long, default_value = "srgb")]
+    color_space: image::ColorSpace,
+    /// Stretch each channel to the full range after rendering
+    #[structopt(long)]
+    auto_levels: bool,
+    /// Percentile of each channel mapped to black by --auto-levels, in
+    /// [0, 1), 0.01 by default
+    #[structopt(long, requires = "auto-levels")]
+    auto_levels_low: Option<f64>,
+    /// Percentile of each channel mapped to white by --auto-levels, in
+    /// (0, 1], 0.99 by default
+    #[structopt(long, requires = "auto-levels")]
+    auto_levels_high: Option<f64>,
+    /// Fast preview shading the first hit with a headlamp, one ray per pixel
+    #[structopt(long, conflicts_with = "roi")]
+    primary_visibility_only: bool,
+    /// Only render the pixel box x0,y0,x1,y1 of the full image
+    #[structopt(long)]
+    roi: Option<Region>,
+    /// Enlarge the rendered image by this factor, at least 1
+    #[structopt(long, parse(try_from_str = parse_upscale_factor))]
+    upscale: Option<usize>,
+    /// Kernel used to upscale: bilinear or lanczos
+    #[structopt(long, default_value = "bilinear")]
+    upscale_kernel: image::UpscaleKernel,
+    /// Smooth the noise with an edge-aware wavelet filter guided by the first hits
+    #[structopt(long, conflicts_with = "roi")]
+    denoise: bool,
+    /// Number of denoising passes from 1 to 10, each one filtering twice as wide
+    #[structopt(
+        long,
+        default_value = "5",
+        parse(try_from_str = parse_denoise_iterations)
+    )]
+    denoise_iterations: u32,
+    /// Show pixels clipped by the output range in magenta
+    #[structopt(long)]
+    clip_warn: bool,
+    /// Only render indirect light, the global illumination pass: light reaching
+    /// the first surface seen after at least one bounce on another surface
+    #[structopt(long, conflicts_with = "primary-visibility-only")]
+    light_paths_only: bool,
+    /// Tone map with extended Reinhard, this linear value becoming white
+    #[structopt(long)]
+    whitepoint: Option<f64>,
+    /// Curve bringing bright colors in the displayable range, clamp (default),
+    /// reinhard or aces
+    #[structopt(long, conflicts_with = "whitepoint")]
+    tone_map: Option<image::ToneMap>,
+    /// Gamma the linear colors are corrected with, 2.2 by default
+    #[structopt(long, conflicts_with_all = &["no-gamma", "srgb"])]
+    gamma: Option<f64>,
+    /// Write linear colors, without gamma correction
+    #[structopt(long)]
+    no_gamma: bool,
+    /// Encode colors with the sRGB transfer function instead of a 2.2 gamma
+    #[structopt(long, conflicts_with = "no-gamma")]
+    srgb: bool,
+    /// Extra samples per pixel on average, spent where the image is the noisiest
+    #[structopt(long, conflicts_with_all = &["roi", "primary-visibility-only"])]
+    progressive_refine: Option<u32>,
+    /// Write the number of samples taken per pixel to this image
+    #[structopt(long, requires = "progressive-refine")]
+    sample_map: Option<String>,
+    /// Also write these passes next to the output: albedo, normal, depth
+    #[structopt(long, use_delimiter = true, conflicts_with = "roi")]
+    aov: Vec<Aov>,
+    /// Show the image in a window as it converges, S saves and Q quits
+    #[cfg(feature = "gui")]
+    #[structopt(
+        long,
+        conflicts_with_all = &["roi", "primary-visibility-only", "progressive-refine"]
+    )]
+    preview_window: bool,
+    /// Render a swatch of this material in a studio instead of the scene:
+    /// lambertian-gray or mirror
+    #[structopt(long, conflicts_with = "frame-all")]
+    preview_material: Option<MaterialOverride>,
+    /// Save the image rendered so far every this many seconds. Built with the
+    /// ctrlc feature, a first Ctrl-C also saves it and stops the render
+    #[structopt(
+        long,
+        conflicts_with_all = &["roi", "primary-visibility-only", "progressive-refine"]
+    )]
+    checkpoint_interval: Option<u64>,
+    /// Render square tiles in parallel instead of bands of lines
+    #[structopt(
+        long,
+        conflicts_with_all = &[
+            "roi",
+            "primary-visibility-only",
+            "progressive-refine",
+            "preview-window",
+            "checkpoint-interval",
+        ]
+    )]
+    tiled: bool,
+    /// Side of the tiles, in pixels, 32 by default
+    #[structopt(long, requires = "tiled")]
+    tile_size: Option<usize>,
+    /// Number of rendering threads, one per core by default
+    #[structopt(long)]
+    threads: Option<usize>,
+    /// Report the elapsed time and an estimate of the time left with the progress
+    #[structopt(long)]
+    progress: bool,
+    /// Don't report progress
+    #[structopt(long, conflicts_with = "progress")]
+    quiet: bool,
+    /// End dim paths at random after a few bounces, instead of tracing them
+    /// up to the bounce limit
+    #[structopt(long)]
+    russian_roulette: bool,
+    /// Place the camera so that the whole scene is in view
+    #[structopt(long)]
+    frame_all: bool,
+    /// Keep the shutter open for this part of the motion of moving spheres,
+    /// from 0 (still, at their start) to 1 (blurred over the whole motion)
+    #[structopt(long)]
+    shutter: Option<f64>,
+    /// Render the scene described in this JSON file instead of the random one,
+    /// options given on the command line take precedence over its settings
+    #[structopt(
+        long,
+        conflicts_with_all = &["preview-material", "scene-seed", "scene-scale"]
+    )]
+    scene: Option<String>,
+    /// Light the scene with this equirectangular picture, .hdr pictures
+    /// keeping their full range, instead of the sky or the scene background
+    #[structopt(long)]
+    environment: Option<String>,
+    /// Seed of the random sphere layout, a different scene is drawn each run otherwise
+    #[structopt(long)]
+    scene_seed: Option<u64>,
+    /// Seed of the random sampling, renders are identical for a given seed.
+    /// Also seeds the sphere layout when --scene-seed isn't given
+    #[structopt(long)]
+    seed: Option<u64>,
+    /// Scale the whole scene and the camera by this positive factor
+    #[structopt(long, default_value = "1.0", parse(try_from_str = parse_scene_scale))]
+    scene_scale: f64,
+    /// Image to write, in the format of its extension: ppm, png, jpg, hdr,
+    /// pfm, bmp or, with the exr feature, exr
     output: String,
 }
 
@@ -36,8 +248,11 @@ impl Viewport {
 #[derive(Debug)]
 struct Camera {
     position: Point,
+    look_at: Point,
+    vup: Vector,
     viewport: Viewport,
     focal: f64,
+    focus_dist: f64,
     lower_left_corner: Point,
     horizontal: Vector,
     vertical: Vector,
@@ -45,6 +260,11 @@ struct Camera {
     v: Vector,
     w: Vector,
     lens_radius: f64,
+    // offset of the image plane from the optical axis, in viewport sizes
+    lens_shift: Vector,
+    // shutter opening and closing instants, rays are cast in between
+    time0: f64,
+    time1: f64,
 }
 
 impl Camera {
@@ -58,54 +278,326 @@ impl Camera {
         aperture: f64,
         focus_dist: f64,
     ) -> Self {
-        let w = vec::unit(&(position - look_at));
-        let u = vec::unit(&vec::cross(&vup, &w));
-        let v = vec::cross(&w, &u);
         let height = 2.0 * (vert_fov.to_radians() / 2.0).tan();
         let viewport = Viewport::new(aspect_ratio * height, height);
-        let horizontal = focus_dist * viewport.width * u;
-        let vertical = focus_dist * viewport.height * v;
-        let lower_left_corner =
-            position - horizontal / 2.0 - vertical / 2.0 - focal * focus_dist * w;
-        Self {
+        let zero = Vector::new(0.0, 0.0, 0.0);
+        let mut camera = Self {
             position,
+            look_at,
+            vup,
             viewport,
             focal,
-            lower_left_corner,
-            horizontal,
-            vertical,
-            u,
-            v,
-            w,
+            focus_dist,
+            lower_left_corner: zero,
+            horizontal: zero,
+            vertical: zero,
+            u: zero,
+            v: zero,
+            w: zero,
             lens_radius: aperture / 2.0,
+            lens_shift: zero,
+            time0: 0.0,
+            time1: 0.0,
+        };
+        camera.orient();
+        camera
+    }
+
+    /// Pinhole camera looking at the center of `world` from the side, far
+    /// enough for the sphere enclosing its bounding box to be in view.
+    /// `margin` is the extra space around it, relative to its radius.
+    /// Unbounded objects like planes are left out, `None` is returned when
+    /// nothing else is left to frame.
+    pub fn frame_scene(world: &dyn Hittable, aspect_ratio: f64, margin: f64) -> Option<Self> {
+        let vert_fov: f64 = 20.0;
+        let bbox = world.bounded_box();
+        if !bbox.is_bounded() {
+            return None;
         }
+        let center = bbox.center();
+        let radius = (1.0 + margin) * (bbox.max() - bbox.min()).length() / 2.0;
+        // the sphere must fit in the narrowest field of view
+        let half_vert = (vert_fov / 2.0).to_radians();
+        let half_horiz = (aspect_ratio * half_vert.tan()).atan();
+        let dist = radius / half_vert.min(half_horiz).sin();
+        let direction = vec::unit(&Vector::new(13.0, 2.0, 3.0));
+        Some(Camera::new(
+            center + dist * direction,
+            center,
+            Vector::new(0.0, 1.0, 0.0),
+            vert_fov,
+            aspect_ratio,
+            1.0,
+            0.0,
+            dist,
+        ))
+    }
+
+    pub fn set_look_at(&mut self, target: Point) {
+        self.look_at = target;
+        self.orient();
+    }
+
+    pub fn set_position(&mut self, pos: Point) {
+        self.position = pos;
+        self.orient();
+    }
+
+    /// Shift the image plane by `shift.x` viewport widths and `shift.y`
+    /// viewport heights without rotating the camera, so vertical lines stay
+    /// parallel when the camera is level
+    pub fn set_lens_shift(&mut self, shift: Vector) {
+        self.lens_shift = shift;
+        self.orient();
+    }
+
+    /// Keep the shutter open from `time0` to `time1`, moving objects are
+    /// blurred along their motion in between
+    pub fn set_shutter(&mut self, time0: f64, time1: f64) {
+        assert!(time0 <= time1, "shutter closes before it opens");
+        self.time0 = time0;
+        self.time1 = time1;
+    }
+
+    // random instant while the shutter is open
+    fn shutter_time(&self) -> f64 {
+        if self.time0 < self.time1 {
+            rng::local().gen_range(self.time0, self.time1)
+        } else {
+            self.time0
+        }
+    }
+
+    // recompute the camera basis and image plane from position and target
+    fn orient(&mut self) {
+        self.w = vec::unit(&(self.position - self.look_at));
+        self.u = vec::unit(&vec::cross(&self.vup, &self.w));
+        self.v = vec::cross(&self.w, &self.u);
+        self.horizontal = self.focus_dist * self.viewport.width * self.u;
+        self.vertical = self.focus_dist * self.viewport.height * self.v;
+        self.lower_left_corner = self.position
+            - self.horizontal / 2.0
+            - self.vertical / 2.0
+            - self.focal * self.focus_dist * self.w
+            + self.lens_shift.x * self.horizontal
+            + self.lens_shift.y * self.vertical;
+    }
+
+    /// Ray through (t, s) leaving the lens at the point mapped from the
+    /// unit square sample `lens`
+    pub fn ray_with_lens(&self, t: f64, s: f64, lens: (f64, f64)) -> Ray {
+        let radius = self.lens_radius * lens.0.sqrt();
+        let angle = 2.0 * std::f64::consts::PI * lens.1;
+        let offset = radius * angle.cos() * self.u + radius * angle.sin() * self.v;
+        Ray::timed(
+            self.position + offset,
+            self.lower_left_corner + t * &self.horizontal + s * &self.vertical
+                - self.position
+                - offset,
+            self.shutter_time(),
+        )
     }
 
     pub fn ray(&self, t: f64, s: f64) -> Ray {
         let rd = self.lens_radius * vec::random_in_unit_disk();
         let offset = rd.x * self.u + rd.y * self.v;
-        Ray::new(
+        Ray::timed(
             self.position + offset,
             self.lower_left_corner + t * &self.horizontal + s * &self.vertical
                 - self.position
                 - offset,
+            self.shutter_time(),
         )
     }
 }
 
+#[derive(Debug)]
+enum MaterialOverride {
+    // every surface scatters with this material
+    Material(Box<dyn material::Material>),
+    // every surface is colored by its normal, without bouncing
+    Normal,
+}
+
+impl std::str::FromStr for MaterialOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lambertian-gray" => Ok(MaterialOverride::Material(Box::new(
+                material::Lambertian::new(Color::new(0.5, 0.5, 0.5)),
+            ))),
+            "mirror" => Ok(MaterialOverride::Material(Box::new(material::Metal::new(
+                Color::new(0.9, 0.9, 0.9),
+                0.0,
+            )))),
+            "normal" => Ok(MaterialOverride::Normal),
+            _ => Err(format!("Unknown material override {}", s)),
+        }
+    }
+}
+
+/// Pixel box [x0, x1) x [y0, y1) of an image, origin on top left
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Region {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Region {
+    pub fn new(x0: usize, y0: usize, x1: usize, y1: usize) -> Self {
+        Self { x0, y0, x1, y1 }
+    }
+
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    /// Error when the region goes past a `width` x `height` image
+    pub fn check_within(&self, width: usize, height: usize) -> Result<(), String> {
+        if self.x1 <= width && self.y1 <= height {
+            Ok(())
+        } else {
+            Err(format!(
+                "Region of interest {},{},{},{} exceeds the {}x{} image",
+                self.x0, self.y0, self.x1, self.y1, width, height
+            ))
+        }
+    }
+}
+
+impl std::str::FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let coords = s
+            .split(',')
+            .map(|c| c.trim().parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid region {}: {}", s, e))?;
+        match coords[..] {
+            [x0, y0, x1, y1] if x0 < x1 && y0 < y1 => Ok(Region::new(x0, y0, x1, y1)),
+            _ => Err(format!("Invalid region {}, expected x0,y0,x1,y1", s)),
+        }
+    }
+}
+
+/// Arbitrary output variable, computed from the first hit of each pixel
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Aov {
+    Albedo,
+    Normal,
+    Depth,
+}
+
+impl Aov {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+        }
+    }
+}
+
+impl std::str::FromStr for Aov {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "albedo" => Ok(Aov::Albedo),
+            "normal" => Ok(Aov::Normal),
+            "depth" => Ok(Aov::Depth),
+            _ => Err(format!("Unknown AOV {}", s)),
+        }
+    }
+}
+
+/// Bounces left along a path, counted separately for each material category
+#[derive(Debug, Clone, Copy)]
+struct BounceBudget {
+    pub glass: u16,
+    pub diffuse: u16,
+}
+
+impl BounceBudget {
+    /// Budget left after a bounce on a `category` material, None if exhausted
+    pub fn spend(&self, category: BounceCategory) -> Option<BounceBudget> {
+        let mut budget = *self;
+        let remaining = match category {
+            BounceCategory::Glass => &mut budget.glass,
+            BounceCategory::Diffuse => &mut budget.diffuse,
+        };
+        *remaining = remaining.checked_sub(1)?;
+        Some(budget)
+    }
+
+    /// Number of bounces spent from this budget to get down to `remaining`
+    pub fn spent(&self, remaining: &BounceBudget) -> u16 {
+        (self.glass - remaining.glass) + (self.diffuse - remaining.diffuse)
+    }
+}
+
 #[derive(Debug)]
 struct RenderSettings {
     pub antialiasing_samples: u16,
-    pub ray_bounce_limit: u16,
-    pub gamma: f64,
+    pub warmup_samples: u16,
+    pub bounces: BounceBudget,
+    // encoding of linear colors for display
+    pub transfer: image::Transfer,
+    pub material_override: Option<MaterialOverride>,
+    pub color_space: image::ColorSpace,
+    // keep linear unclamped colors, for float output formats
+    pub hdr_output: bool,
+    // minimal distance of a hit to the ray origin, avoids self intersections
+    pub ray_epsilon: f64,
+    // only gather light reaching the first surface after bouncing on another
+    // one, leaving out the direct term
+    pub indirect_only: bool,
+    pub tone_map: image::ToneMap,
+    pub sampler: sampler::Sampler,
+    // seed of the random sampling, drawn from the system when absent
+    pub seed: Option<u64>,
+    // light of the rays missing every object
+    pub background: Box<dyn Background>,
+    pub progress: progress::ProgressStyle,
+    // sample point lights at each diffuse hit, on top of the paths
+    pub direct_lighting: bool,
+    pub lights: Vec<light::PointLight>,
+    // end paths at random depending on their attenuation, off for
+    // deterministic renders
+    pub russian_roulette: bool,
 }
 
 impl std::default::Default for RenderSettings {
     fn default() -> Self {
         RenderSettings {
             antialiasing_samples: 1,
-            ray_bounce_limit: 0,
-            gamma: 1.0,
+            warmup_samples: 0,
+            bounces: BounceBudget {
+                glass: 0,
+                diffuse: 0,
+            },
+            transfer: image::Transfer::Gamma(1.0),
+            material_override: None,
+            color_space: image::ColorSpace::Srgb,
+            hdr_output: false,
+            ray_epsilon: 0.001,
+            indirect_only: false,
+            tone_map: image::ToneMap::Clamp,
+            sampler: sampler::Sampler::Random,
+            seed: None,
+            background: Box::new(GradientSky::default()),
+            progress: progress::ProgressStyle::Percent,
+            direct_lighting: false,
+            lights: Vec::new(),
+            russian_roulette: false,
         }
     }
 }
@@ -115,32 +607,148 @@ impl RenderSettings {
         self.antialiasing_samples = val;
         self
     }
-    pub fn ray_bounce_limit(&mut self, val: u16) -> &mut Self {
-        self.ray_bounce_limit = val;
+    pub fn warmup_samples(&mut self, val: u16) -> &mut Self {
+        self.warmup_samples = val;
+        self
+    }
+    pub fn glass_bounces(&mut self, val: u16) -> &mut Self {
+        self.bounces.glass = val;
+        self
+    }
+    pub fn diffuse_bounces(&mut self, val: u16) -> &mut Self {
+        self.bounces.diffuse = val;
+        self
+    }
+    pub fn gamma(&mut self, val: f64) -> &mut Self {
+        assert!(val > 0.0, "Gamma must be positive, got {}", val);
+        self.transfer = image::Transfer::Gamma(1.0 / val);
+        self
+    }
+    pub fn transfer(&mut self, val: image::Transfer) -> &mut Self {
+        self.transfer = val;
+        self
+    }
+    pub fn material_override(&mut self, val: Option<MaterialOverride>) -> &mut Self {
+        self.material_override = val;
+        self
+    }
+    pub fn color_space(&mut self, val: image::ColorSpace) -> &mut Self {
+        self.color_space = val;
+        self
+    }
+    pub fn hdr_output(&mut self, val: bool) -> &mut Self {
+        self.hdr_output = val;
+        self
+    }
+    pub fn ray_epsilon(&mut self, val: f64) -> &mut Self {
+        self.ray_epsilon = val;
+        self
+    }
+    pub fn indirect_only(&mut self, val: bool) -> &mut Self {
+        self.indirect_only = val;
+        self
+    }
+    pub fn tone_map(&mut self, val: image::ToneMap) -> &mut Self {
+        self.tone_map = val;
         self
     }
-    pub fn gamma(&mut self, val: u16) -> &mut Self {
-        self.gamma = 1.0 / val as f64;
+    pub fn sampler(&mut self, val: sampler::Sampler) -> &mut Self {
+        self.sampler = val;
+        self
+    }
+    pub fn seed(&mut self, val: Option<u64>) -> &mut Self {
+        self.seed = val;
+        self
+    }
+    pub fn background(&mut self, val: Box<dyn Background>) -> &mut Self {
+        self.background = val;
+        self
+    }
+    pub fn progress(&mut self, val: progress::ProgressStyle) -> &mut Self {
+        self.progress = val;
+        self
+    }
+    pub fn direct_lighting(&mut self, val: bool) -> &mut Self {
+        self.direct_lighting = val;
+        self
+    }
+    pub fn lights(&mut self, val: Vec<light::PointLight>) -> &mut Self {
+        self.lights = val;
+        self
+    }
+    pub fn russian_roulette(&mut self, val: bool) -> &mut Self {
+        self.russian_roulette = val;
         self
     }
 }
 
 fn main() {
-    let aspect_ratio = 3.0 / 2.0;
-    let opt = Options::from_args();
+    let mut opt = Options::from_args();
+    if opt.width == 0 || opt.height == Some(0) {
+        eprintln!("Image dimensions must be positive");
+        std::process::exit(1);
+    }
+    if opt.tile_size == Some(0) {
+        eprintln!("Tile size must be positive");
+        std::process::exit(1);
+    }
+    if let Some(shutter) = opt.shutter {
+        if shutter.is_nan() || shutter < 0.0 {
+            eprintln!("Shutter time can't be negative");
+            std::process::exit(1);
+        }
+    }
+    let levels = match auto_levels_option(&opt) {
+        Ok(levels) => levels,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(threads) = opt.threads {
+        if let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+        {
+            eprintln!("Can't start {} threads: {}", threads, e);
+            std::process::exit(1);
+        }
+    }
     // image
-    let mut img = image::Image::new(
-        opt.width as usize,
-        (opt.width as f64 / aspect_ratio) as usize,
-    );
+    let (width, height, aspect_ratio) = image_size(opt.width, opt.height);
+    if let Err(e) = image::check_resolution(width, height, opt.max_resolution) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let region = opt.roi.unwrap_or_else(|| Region::new(0, 0, width, height));
+    if let Err(e) = region.check_within(width, height) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    if let Some(factor) = opt.upscale {
+        let (width, height) = (region.width(), region.height());
+        let upscaled = match (width.checked_mul(factor), height.checked_mul(factor)) {
+            (Some(width), Some(height)) => {
+                image::check_resolution(width, height, opt.max_resolution).map(|_| ())
+            }
+            _ => Err(error::RenderError::ResolutionOverflow { width, height }),
+        };
+        if let Err(e) = upscaled {
+            eprintln!("Can't upscale by {}: {}", factor, e);
+            std::process::exit(1);
+        }
+    }
+    let mut img = image::Image::new(region.width(), region.height());
     // camera
     let vert_fov = 20.0;
     let focal_length = 1.0;
-    let origin = Point::new(13.0, 2.0, 3.0);
-    let look_at = Point::new(0.0, 0.0, 0.0);
+    // framing is preserved when scaling the scene
+    let scale = opt.scene_scale;
+    let origin = scale * Point::new(13.0, 2.0, 3.0);
+    let look_at = scale * Point::new(0.0, 0.0, 0.0);
     let vup = Point::new(0.0, 1.0, 0.0);
-    let aperture = 0.1;
-    let dist_to_focus = 10.0;
+    let aperture = scale * 0.1;
+    let dist_to_focus = scale * 10.0;
     let camera = Camera::new(
         origin,
         look_at,
@@ -152,12 +760,950 @@ fn main() {
         dist_to_focus,
     );
     // world
-    let mut spheres = vec![
+    let mut rng = match opt.scene_seed.or(opt.seed) {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let mut scene_settings = None;
+    let mut lights = Vec::new();
+    let (world, camera): (World, Camera) = if let Some(path) = &opt.scene {
+        let scene = match scene::Scene::from_json(path, aspect_ratio) {
+            Ok(scene) => scene,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        if scene.world.is_empty() {
+            eprintln!("{} has no objects", path);
+        } else if !opt.quiet {
+            eprintln!("{} objects in {}", scene.world.len(), path);
+        }
+        lights = scene.lights;
+        scene_settings = Some(scene.settings);
+        (scene.world, scene.camera)
+    } else {
+        let (spheres, camera) = match opt.preview_material.take() {
+            Some(MaterialOverride::Material(material)) => {
+                (studio_scene(material), studio_camera(aspect_ratio))
+            }
+            Some(MaterialOverride::Normal) => {
+                eprintln!("normal is not a material, it can't be previewed");
+                std::process::exit(1);
+            }
+            None => (random_scene(&mut rng), camera),
+        };
+        let objects = spheres
+            .into_iter()
+            .map(|sphere| Box::new(sphere) as Box<dyn Hittable>)
+            .collect();
+        (HittableVec::new(objects), camera)
+    };
+    let world = Scale::new(world, scale);
+    let mut camera = if opt.frame_all {
+        match Camera::frame_scene(&world, aspect_ratio, 0.1) {
+            Some(camera) => camera,
+            None => {
+                eprintln!("Nothing to frame, the scene has only unbounded objects");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        camera
+    };
+    if let Some(shutter) = opt.shutter {
+        camera.set_shutter(0.0, shutter);
+    }
+    // render
+    let mut settings = RenderSettings::default();
+    if let Err(e) =
+        apply_quality_options(&opt, width, height, scene_settings.as_ref(), &mut settings)
+    {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    if opt.ray_budget.is_some() {
+        let samples = settings.antialiasing_samples;
+        eprintln!("Effective samples per pixel: {}", samples);
+    }
+    settings
+        .warmup_samples(opt.warmup)
+        .material_override(opt.material_override.take())
+        .color_space(opt.color_space)
+        .hdr_output(is_hdr_format(&opt.output) || opt.clip_warn)
+        .ray_epsilon(0.001 * scale)
+        .indirect_only(opt.light_paths_only)
+        .sampler(opt.sampler)
+        .seed(opt.seed)
+        .lights(lights)
+        .russian_roulette(opt.russian_roulette)
+        .progress(if opt.quiet {
+            progress::ProgressStyle::Quiet
+        } else if opt.progress {
+            progress::ProgressStyle::Eta
+        } else {
+            progress::ProgressStyle::Percent
+        })
+        .tone_map(tone_map_option(&opt));
+    if let Some(path) = &opt.environment {
+        match background::EnvironmentMap::open(path) {
+            Ok(env) => settings.background(Box::new(env)),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+    }
+    let start = std::time::Instant::now();
+    if opt.primary_visibility_only {
+        fill_image_primary_visibility(&mut img, &settings, &camera, &world);
+    } else if opt.roi.is_some() {
+        render_region(&mut img, width, height, &region, &settings, &camera, &world);
+    } else if let Some(extra) = opt.progressive_refine {
+        let samples = fill_image_progressive(&mut img, &settings, &camera, &world, extra);
+        let total: u64 = samples.iter().map(|&n| n as u64).sum();
+        eprintln!(
+            "Samples per pixel: min {}, mean {:.1}, max {}",
+            samples.iter().min().unwrap_or(&0),
+            total as f64 / samples.len() as f64,
+            samples.iter().max().unwrap_or(&0)
+        );
+        if let Some(path) = &opt.sample_map {
+            save(path, &refine::sample_map(&samples, img.width, img.height));
+        }
+    } else if opt.tiled {
+        let tile_size = opt.tile_size.unwrap_or(32);
+        fill_image_tiled(&mut img, &settings, &camera, &world, tile_size);
+    } else if !fill_image_live(&opt, &mut img, &settings, &camera, &world) {
+        let interval = opt.checkpoint_interval.map(Duration::from_secs);
+        let mut checkpoint = checkpoint::Checkpoint::new(interval);
+        #[cfg(feature = "ctrlc")]
+        {
+            if let Err(e) = checkpoint.catch_interrupt() {
+                eprintln!("Ctrl-C won't save the render: {}", e);
+            }
+        }
+        let path = &opt.output;
+        let finished = fill_image(&mut img, &settings, &camera, &world, |partial| {
+            handle_checkpoint(checkpoint.poll(), path, partial)
+        });
+        if !finished {
+            eprintln!("\nRender interrupted, partial image saved to {}", path);
+            return;
+        }
+    }
+    if settings.progress != progress::ProgressStyle::Quiet {
+        // passes rendered on this thread alone
+        stats::flush();
+        let (rays, tests) = stats::counters();
+        let summary = stats::format_summary(img.width, img.height, start.elapsed(), rays, tests);
+        eprintln!("{}", summary);
+    }
+    if opt.denoise {
+        let aovs = [Aov::Albedo, Aov::Normal, Aov::Depth];
+        let guides = fill_aovs(&aovs, width, height, &settings, &camera, &world);
+        let guides = denoise::Guides {
+            albedo: &guides[0],
+            normal: &guides[1],
+            depth: &guides[2],
+        };
+        img = denoise::a_trous(&img, &guides, opt.denoise_iterations);
+    }
+    if opt.clip_warn {
+        settings.hdr_output(is_hdr_format(&opt.output));
+        highlight_clipped(&mut img, &settings);
+    }
+    if opt.auto_levels {
+        img.auto_levels(levels.0, levels.1);
+    }
+    if let Some(factor) = opt.upscale {
+        img = img.upscale(factor, opt.upscale_kernel);
+    }
+    save(&opt.output, &img);
+    if !opt.aov.is_empty() {
+        let aovs = fill_aovs(&opt.aov, width, height, &settings, &camera, &world);
+        for (aov, mut img) in opt.aov.iter().zip(aovs) {
+            if *aov == Aov::Depth {
+                // closest hits are dark, misses and farthest hits are white
+                let far = img.data.iter().map(|c| c.red).fold(0.0, f64::max);
+                for px in img.data.iter_mut() {
+                    let depth = if px.red > 0.0 { px.red / far } else { 1.0 };
+                    *px = Color::new(depth, depth, depth);
+                }
+            }
+            img.data.iter_mut().for_each(|c| c.clamp(0.0, 0.999));
+            save(&aov_path(&opt.output, *aov), &img);
+        }
+    }
+}
+
+fn save(path: &str, img: &image::Image) {
+    if let Err(e) = img.save(path) {
+        eprintln!("Failed to write {}: {}", path, e);
+        std::process::exit(1);
+    }
+}
+
+// floating point formats get the linear colors, without gamma or clamping
+fn is_hdr_format(path: &str) -> bool {
+    has_extension(path, "pfm")
+        || has_extension(path, "hdr")
+        || (cfg!(feature = "exr") && has_extension(path, "exr"))
+}
+
+fn has_extension(path: &str, ext: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+}
+
+// output.ppm becomes output.<aov>.ppm
+fn aov_path(output: &str, aov: Aov) -> String {
+    let path = std::path::Path::new(output);
+    match path.extension() {
+        Some(ext) => path
+            .with_extension(format!("{}.{}", aov.name(), ext.to_string_lossy()))
+            .to_string_lossy()
+            .into_owned(),
+        None => format!("{}.{}", output, aov.name()),
+    }
+}
+
+fn random_in_hemisphere(normal: &Vector) -> Vector {
+    let random_unit = vec::random_unit_vector();
+    if vec::dot(&random_unit, normal) > 0.0 {
+        random_unit
+    } else {
+        -random_unit
+    }
+}
+
+// bounces always traced before Russian roulette may end a path
+const ROULETTE_BOUNCES: u16 = 3;
+
+fn ray_color(
+    ray: &Ray,
+    world: &dyn Hittable,
+    bounces: BounceBudget,
+    settings: &RenderSettings,
+) -> Color {
+    // light gathered so far, and product of the attenuations of the surfaces
+    // the path bounced on
+    let mut color = image::colors::BLACK;
+    let mut throughput = image::colors::WHITE;
+    let mut ray = Ray::timed(ray.origin, ray.direction, ray.time);
+    let mut bounces = bounces;
+    loop {
+        stats::count_ray();
+        let hit = match world.hit(
+            &ray,
+            Interval::new(settings.ray_epsilon, Interval::UNIVERSE.max),
+        ) {
+            Some(hit) => hit,
+            None => {
+                // the sky seen directly or lighting the first surface is direct light
+                if settings.indirect_only && settings.bounces.spent(&bounces) < 2 {
+                    return color;
+                }
+                return color + throughput * settings.background.sample(&ray);
+            }
+        };
+        let material: &dyn Material = match &settings.material_override {
+            None => hit.material.as_ref(),
+            Some(MaterialOverride::Material(material)) => material.as_ref(),
+            Some(MaterialOverride::Normal) => {
+                let n = hit.normal;
+                return color + throughput * (0.5 * Color::new(n.x + 1.0, n.y + 1.0, n.z + 1.0));
+            }
+        };
+        // lights seen directly or lighting the first surface are direct light
+        let emitted = if settings.indirect_only && settings.bounces.spent(&bounces) < 2 {
+            image::colors::BLACK
+        } else {
+            material.emitted()
+        };
+        // point lights lighting the first surface are direct light
+        let lit = settings.direct_lighting
+            && !(settings.indirect_only && settings.bounces.spent(&bounces) < 1);
+        // ray bounced too many times on this kind of material,
+        // no more light is gathered
+        bounces = match bounces.spend(material.bounce_category()) {
+            Some(bounces) => bounces,
+            None => return color + throughput * emitted,
+        };
+        let effect = material.scatter(&ray, &hit);
+        let direct = if lit && !effect.is_specular {
+            direct_light(&ray, &hit, material, world, settings)
+        } else {
+            image::colors::BLACK
+        };
+        color += throughput * (emitted + direct);
+        match effect.scattered {
+            None => return color,
+            Some(scattered) => {
+                throughput = throughput * effect.attenuation;
+                // past a few bounces, paths are ended with a probability
+                // growing as they dim, the surviving ones being brightened
+                // to make up for the others
+                if settings.russian_roulette && settings.bounces.spent(&bounces) >= ROULETTE_BOUNCES
+                {
+                    let survival = throughput
+                        .red
+                        .max(throughput.green)
+                        .max(throughput.blue)
+                        .min(1.0);
+                    if rng::local().gen_range(0.0, 1.0) >= survival {
+                        return color;
+                    }
+                    throughput /= survival;
+                }
+                ray = scattered;
+            }
+        }
+    }
+}
+
+/// Light reflected toward `ray` by the `hit` surface of `material` from the
+/// point lights it sees
+fn direct_light(
+    ray: &Ray,
+    hit: &ray::HitRecord,
+    material: &dyn Material,
+    world: &dyn Hittable,
+    settings: &RenderSettings,
+) -> Color {
+    let to_viewer = -vec::unit(&ray.direction);
+    let mut light = image::colors::BLACK;
+    for point in &settings.lights {
+        let to_light = point.position - hit.point;
+        // the light is at t = 1 along the shadow ray
+        let shadow = Ray::timed(hit.point, to_light, ray.time);
+        stats::count_ray();
+        if world
+            .hit(&shadow, Interval::new(settings.ray_epsilon, 1.0))
+            .is_some()
+        {
+            continue;
+        }
+        let dist_squared = to_light.length_squared();
+        let light_dir = to_light / dist_squared.sqrt();
+        let cos = vec::dot(&hit.normal, &light_dir).max(0.0);
+        let brdf = material.eval_brdf(hit, &light_dir, &to_viewer);
+        light += (cos / dist_squared) * brdf * point.intensity;
+    }
+    light
+}
+
+fn primary_visibility_color(ray: &Ray, world: &dyn Hittable, settings: &RenderSettings) -> Color {
+    stats::count_ray();
+    if let Some(hit) = world.hit(
+        ray,
+        Interval::new(settings.ray_epsilon, Interval::UNIVERSE.max),
+    ) {
+        // headlamp: light comes from the camera, with some ambient
+        let light_dir = -vec::unit(&ray.direction);
+        let shade = vec::dot(&hit.normal, &light_dir).max(0.2);
+        return shade * hit.material.albedo(&hit);
+    }
+    settings.background.sample(ray)
+}
+
+/// Render band of lines by band of lines, calling `on_band` with the image
+/// rendered so far after each band. Stops early and returns false when
+/// `on_band` does.
+fn fill_image<F: FnMut(&image::Image) -> bool>(
+    img: &mut image::Image,
+    settings: &RenderSettings,
+    camera: &Camera,
+    world: &dyn Hittable,
+    on_band: F,
+) -> bool {
+    let (width, height) = (img.width, img.height);
+    let region = Region::new(0, 0, width, height);
+    render_region_until(
+        img, width, height, &region, settings, camera, world, on_band,
+    )
+}
+
+// save `img` if `action` asks for it, returns false once the render should stop
+fn handle_checkpoint(action: checkpoint::CheckpointAction, path: &str, img: &image::Image) -> bool {
+    match action {
+        checkpoint::CheckpointAction::Continue => true,
+        checkpoint::CheckpointAction::Save => {
+            save(path, img);
+            true
+        }
+        checkpoint::CheckpointAction::SaveAndStop => {
+            save(path, img);
+            false
+        }
+    }
+}
+
+/// Render `region` of a `full_width` x `full_height` image into `img`,
+/// which has the size of the region. Camera framing is the one of the full image.
+fn render_region(
+    img: &mut image::Image,
+    full_width: usize,
+    full_height: usize,
+    region: &Region,
+    settings: &RenderSettings,
+    camera: &Camera,
+    world: &dyn Hittable,
+) {
+    render_region_until(
+        img,
+        full_width,
+        full_height,
+        region,
+        settings,
+        camera,
+        world,
+        |_| true,
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_region_until<F: FnMut(&image::Image) -> bool>(
+    img: &mut image::Image,
+    full_width: usize,
+    full_height: usize,
+    region: &Region,
+    settings: &RenderSettings,
+    camera: &Camera,
+    world: &dyn Hittable,
+    mut on_band: F,
+) -> bool {
+    let size = (full_width as f64, full_height as f64);
+    let width = img.width;
+    let progress = progress::Progress::new(settings.progress, region.width() * region.height());
+    // lines of a band are rendered in parallel
+    let band = 4 * rayon::current_num_threads();
+    for band_start in (0..img.height).step_by(band) {
+        let band_end = (band_start + band).min(img.height);
+        let pixels = &mut img.data[band_start * width..band_end * width];
+        pixels
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(|(index, pixels)| {
+                let line = region.y0 + band_start + index;
+                for (px, col) in pixels.iter_mut().zip(region.x0..region.x1) {
+                    *px = render_pixel(col, line, size, settings, camera, world);
+                }
+                stats::flush();
+                progress.advance(pixels.len());
+            });
+        if !on_band(img) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Corrected color of the pixel (`col`, `line`) of an image of `size`
+fn render_pixel(
+    col: usize,
+    line: usize,
+    size: (f64, f64),
+    settings: &RenderSettings,
+    camera: &Camera,
+    world: &dyn Hittable,
+) -> Color {
+    // each worker thread draws from its own generator. With a seed, the
+    // generator restarts at each pixel from the seed and the pixel index, so
+    // the image doesn't depend on which thread renders which pixel.
+    if let Some(seed) = settings.seed {
+        let index = line * size.0 as usize + col;
+        rng::reseed(rng::stream_seed(seed, index as u64));
+    }
+    let mut rng = rng::local();
+    let samples = settings.antialiasing_samples as usize;
+    let warmup = settings.warmup_samples as usize;
+    // the sampler points all go to the kept samples, warmup samples are
+    // drawn at random so they don't take the first strata
+    let offsets = settings.sampler.pixel_samples(samples, &mut rng);
+    let lens = settings.sampler.lens_samples(samples, &mut rng);
+    let mut points = offsets.into_iter().zip(lens);
+    let mut drawn = 0;
+    let mut px = accumulate(samples, warmup, || {
+        drawn += 1;
+        let (offset, lens) = if drawn <= warmup {
+            let mut uniform = || (rng.gen_range(0.0, 1.0), rng.gen_range(0.0, 1.0));
+            (uniform(), uniform())
+        } else {
+            points.next().unwrap()
+        };
+        let ray = sampled_ray(camera, col, line, size, offset, lens);
+        ray_color(&ray, world, settings.bounces, settings)
+    });
+    correct(&mut px, settings);
+    px
+}
+
+/// Render tiles of `tile_size` pixels in parallel, edge tiles being cut to
+/// the image
+fn fill_image_tiled(
+    img: &mut image::Image,
+    settings: &RenderSettings,
+    camera: &Camera,
+    world: &dyn Hittable,
+    tile_size: usize,
+) {
+    let (width, height) = (img.width, img.height);
+    let size = (width as f64, height as f64);
+    let mut tiles = Vec::new();
+    for y0 in (0..height).step_by(tile_size) {
+        for x0 in (0..width).step_by(tile_size) {
+            let (x1, y1) = ((x0 + tile_size).min(width), (y0 + tile_size).min(height));
+            tiles.push(Region::new(x0, y0, x1, y1));
+        }
+    }
+    let progress = progress::Progress::new(settings.progress, width * height);
+    let img = Mutex::new(img);
+    tiles.par_iter().for_each(|tile| {
+        let pixels: Vec<Color> = (tile.y0..tile.y1)
+            .flat_map(|line| (tile.x0..tile.x1).map(move |col| (col, line)))
+            .map(|(col, line)| render_pixel(col, line, size, settings, camera, world))
+            .collect();
+        stats::flush();
+        // only finished tiles take the lock
+        let mut img = img.lock().unwrap();
+        for (row, line) in pixels.chunks(tile.width()).zip(tile.y0..tile.y1) {
+            img.data[line * width + tile.x0..line * width + tile.x1].copy_from_slice(row);
+        }
+        progress.advance(pixels.len());
+    });
+}
+
+/// Render with the configured samples, then spend `extra` samples per pixel
+/// on average where the variance is the highest. Returns the number of
+/// samples taken for each pixel.
+fn fill_image_progressive(
+    img: &mut image::Image,
+    settings: &RenderSettings,
+    camera: &Camera,
+    world: &dyn Hittable,
+    extra: u32,
+) -> Vec<u32> {
+    if let Some(seed) = settings.seed {
+        rng::reseed(seed);
+    }
+    let mut rng = rng::local();
+    let size = (img.width as f64, img.height as f64);
+    let refined = refine::progressive_refine(
+        img.width,
+        img.height,
+        settings.antialiasing_samples as u32,
+        extra,
+        |col, line| {
+            let ray = jittered_ray(camera, col, line, size, &mut rng);
+            ray_color(&ray, world, settings.bounces, settings)
+        },
+    );
+    for (px, color) in img.data.iter_mut().zip(refined.image.data) {
+        *px = color;
+        correct(px, settings);
+    }
+    refined.samples
+}
+
+/// Render one sample per pixel at a time, calling `on_pass` with the image
+/// converged so far after each pass. Stops early when `on_pass` returns false.
+#[cfg(feature = "gui")]
+fn fill_image_passes<F: FnMut(&image::Image) -> bool>(
+    img: &mut image::Image,
+    settings: &RenderSettings,
+    camera: &Camera,
+    world: &dyn Hittable,
+    mut on_pass: F,
+) {
+    if let Some(seed) = settings.seed {
+        rng::reseed(seed);
+    }
+    let mut rng = rng::local();
+    let size = (img.width as f64, img.height as f64);
+    let mut sums = vec![image::colors::BLACK; img.data.len()];
+    let warmup = settings.warmup_samples as usize;
+    let passes = warmup + settings.antialiasing_samples as usize;
+    for pass in 0..passes {
+        for (index, sum) in sums.iter_mut().enumerate() {
+            let ray = jittered_ray(camera, index % img.width, index / img.width, size, &mut rng);
+            let color = ray_color(&ray, world, settings.bounces, settings);
+            // warmup samples are discarded
+            if pass >= warmup {
+                *sum += color;
+            }
+        }
+        if pass < warmup {
+            continue;
+        }
+        let count = (pass - warmup + 1) as f64;
+        for (px, sum) in img.data.iter_mut().zip(&sums) {
+            *px = sum / count;
+            correct(px, settings);
+        }
+        if !on_pass(img) {
+            break;
+        }
+    }
+}
+
+/// Render pass by pass in a preview window when asked for,
+/// returns false if the image was not rendered
+#[cfg(feature = "gui")]
+fn fill_image_live(
+    opt: &Options,
+    img: &mut image::Image,
+    settings: &RenderSettings,
+    camera: &Camera,
+    world: &dyn Hittable,
+) -> bool {
+    if !opt.preview_window {
+        return false;
+    }
+    let mut window = preview::Preview::new(img.width, img.height);
+    fill_image_passes(img, settings, camera, world, |pass| {
+        match window.show(pass) {
+            preview::PreviewAction::Continue => true,
+            preview::PreviewAction::Save => {
+                save(&opt.output, pass);
+                true
+            }
+            preview::PreviewAction::Quit => false,
+        }
+    });
+    true
+}
+
+#[cfg(not(feature = "gui"))]
+fn fill_image_live(
+    _opt: &Options,
+    _img: &mut image::Image,
+    _settings: &RenderSettings,
+    _camera: &Camera,
+    _world: &dyn Hittable,
+) -> bool {
+    false
+}
+
+// ray through a random point of pixel (col, line) of an image of `size`
+fn jittered_ray<R: Rng>(
+    camera: &Camera,
+    col: usize,
+    line: usize,
+    size: (f64, f64),
+    rng: &mut R,
+) -> Ray {
+    let (width, height) = size;
+    let u = (col as f64 + rng.gen_range(0.0, 1.0)) / (width - 1.0);
+    // render starts on top left
+    let v = (height - (line as f64 + rng.gen_range(0.0, 1.0))) / (height - 1.0);
+    camera.ray(u, v)
+}
+
+// ray through the point at `offset` in pixel (col, line) of an image of `size`
+fn sampled_ray(
+    camera: &Camera,
+    col: usize,
+    line: usize,
+    size: (f64, f64),
+    offset: (f64, f64),
+    lens: (f64, f64),
+) -> Ray {
+    let (width, height) = size;
+    let u = (col as f64 + offset.0) / (width - 1.0);
+    // render starts on top left
+    let v = (height - (line as f64 + offset.1)) / (height - 1.0);
+    camera.ray_with_lens(u, v, lens)
+}
+
+// single ray through each pixel center, no global illumination
+fn fill_image_primary_visibility(
+    img: &mut image::Image,
+    settings: &RenderSettings,
+    camera: &Camera,
+    world: &dyn Hittable,
+) {
+    for line in 0..img.height {
+        for col in 0..img.width {
+            let u = col as f64 / (img.width as f64 - 1.0);
+            // render starts on top left
+            let v = (img.height - 1 - line) as f64 / (img.height as f64 - 1.0);
+            let mut color = primary_visibility_color(&camera.ray(u, v), world, settings);
+            correct(&mut color, settings);
+            img.data[line * img.width + col] = color;
+        }
+    }
+}
+
+/// Compute the requested AOVs in a single pass, from the first hit of a ray
+/// through each pixel center. Depth is the distance to the camera, 0 for misses.
+fn fill_aovs(
+    aovs: &[Aov],
+    width: usize,
+    height: usize,
+    settings: &RenderSettings,
+    camera: &Camera,
+    world: &dyn Hittable,
+) -> Vec<image::Image> {
+    let mut images: Vec<_> = aovs
+        .iter()
+        .map(|_| image::Image::new(width, height))
+        .collect();
+    for line in 0..height {
+        for col in 0..width {
+            let u = col as f64 / (width as f64 - 1.0);
+            // render starts on top left
+            let v = (height - 1 - line) as f64 / (height as f64 - 1.0);
+            let ray = camera.ray(u, v);
+            let hit = match world.hit(
+                &ray,
+                Interval::new(settings.ray_epsilon, Interval::UNIVERSE.max),
+            ) {
+                Some(hit) => hit,
+                None => continue,
+            };
+            for (aov, img) in aovs.iter().zip(images.iter_mut()) {
+                img.data[line * width + col] = match aov {
+                    Aov::Albedo => hit.material.albedo(&hit),
+                    Aov::Normal => {
+                        let n = hit.normal;
+                        0.5 * Color::new(n.x + 1.0, n.y + 1.0, n.z + 1.0)
+                    }
+                    Aov::Depth => {
+                        let depth = hit.t * ray.direction.length();
+                        Color::new(depth, depth, depth)
+                    }
+                };
+            }
+        }
+    }
+    images
+}
+
+fn correct(color: &mut Color, settings: &RenderSettings) {
+    if settings.color_space != image::ColorSpace::Srgb {
+        *color = color.transform(&settings.color_space.matrix_from_srgb());
+    }
+    if !settings.hdr_output {
+        encode(color, settings);
+    }
+}
+
+fn encode(color: &mut Color, settings: &RenderSettings) {
+    *color = settings.tone_map.map(color);
+    *color = settings.transfer.encode(color);
+    color.clamp(0.0, 0.999);
+}
+
+/// Paint pixels of a linear `img` exceeding the displayable range in
+/// magenta, and encode the others for `settings`
+fn highlight_clipped(img: &mut image::Image, settings: &RenderSettings) {
+    let clipped = img.clipped_mask(1.0);
+    for (px, clipped) in img.data.iter_mut().zip(clipped) {
+        if clipped {
+            *px = image::colors::MAGENTA;
+        } else if !settings.hdr_output {
+            encode(px, settings);
+        }
+    }
+}
+
+/// Width and height of the image, and the aspect ratio the camera frames
+fn image_size(width: u16, height: Option<u16>) -> (usize, usize, f64) {
+    match height {
+        Some(height) => (
+            width as usize,
+            height as usize,
+            width as f64 / height as f64,
+        ),
+        None => {
+            let aspect_ratio = 3.0 / 2.0;
+            let height = (width as f64 / aspect_ratio) as usize;
+            (width as usize, height, aspect_ratio)
+        }
+    }
+}
+
+/// Set the samples, bounces and color encoding given on the command line,
+/// for a `width` x `height` image, over the settings of the `scene` if any
+fn apply_quality_options(
+    opt: &Options,
+    width: usize,
+    height: usize,
+    scene: Option<&scene::SceneSettings>,
+    settings: &mut RenderSettings,
+) -> Result<(), String> {
+    settings
+        .aa_samples(100)
+        .glass_bounces(50)
+        .diffuse_bounces(50);
+    if let Some(scene) = scene {
+        scene.apply(settings);
+    }
+    // options given explicitly override the scene
+    let samples = match opt.ray_budget {
+        Some(budget) => Some(samples_from_budget(budget, width, height)),
+        None => opt.samples,
+    };
+    if let Some(samples) = samples {
+        settings.aa_samples(samples);
+    }
+    if settings.antialiasing_samples == 0 {
+        return Err("Samples per pixel must be at least 1".to_string());
+    }
+    let gamma = opt.gamma.unwrap_or(2.2);
+    if gamma.is_nan() || gamma <= 0.0 {
+        return Err(format!("Gamma must be positive, got {}", gamma));
+    }
+    let (glass, diffuse) = match opt.bounces {
+        Some(bounces) => (Some(bounces), Some(bounces)),
+        None => (opt.glass_bounces, opt.diffuse_bounces),
+    };
+    if let Some(glass) = glass {
+        settings.glass_bounces(glass);
+    }
+    if let Some(diffuse) = diffuse {
+        settings.diffuse_bounces(diffuse);
+    }
+    if opt.srgb {
+        settings.transfer(image::Transfer::Srgb);
+    } else {
+        settings.gamma(if opt.no_gamma { 1.0 } else { gamma });
+    }
+    Ok(())
+}
+
+/// Low and high percentiles of --auto-levels, 1% and 99% by default
+fn auto_levels_option(opt: &Options) -> Result<(f64, f64), String> {
+    let low = opt.auto_levels_low.unwrap_or(0.01);
+    let high = opt.auto_levels_high.unwrap_or(0.99);
+    if (0.0..high).contains(&low) && high <= 1.0 {
+        Ok((low, high))
+    } else {
+        Err(format!(
+            "Auto levels percentiles must satisfy 0 <= low < high <= 1, got {} and {}",
+            low, high
+        ))
+    }
+}
+
+/// Tone map chosen on the command line, clamping by default
+fn tone_map_option(opt: &Options) -> image::ToneMap {
+    match opt.whitepoint {
+        Some(white) => image::ToneMap::ReinhardExtended { white },
+        None => opt.tone_map.unwrap_or(image::ToneMap::Clamp),
+    }
+}
+
+/// Number of denoising passes, past 10 the filter spans more than a
+/// thousand pixels and its step no longer fits
+fn parse_denoise_iterations(s: &str) -> Result<u32, String> {
+    let iterations: u32 = s.parse().map_err(|e| format!("{}", e))?;
+    if (1..=10).contains(&iterations) {
+        Ok(iterations)
+    } else {
+        Err(format!(
+            "Denoise iterations must be between 1 and 10, got {}",
+            iterations
+        ))
+    }
+}
+
+/// Factor enlarging the rendered image, 0 would leave nothing to save
+fn parse_upscale_factor(s: &str) -> Result<usize, String> {
+    let factor: usize = s.parse().map_err(|e| format!("{}", e))?;
+    if factor >= 1 {
+        Ok(factor)
+    } else {
+        Err("Upscale factor must be at least 1".to_string())
+    }
+}
+
+/// Factor scaling the scene, 0 would collapse it and negative values would
+/// turn its bounding boxes inside out
+fn parse_scene_scale(s: &str) -> Result<f64, String> {
+    let scale: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if scale.is_finite() && scale > 0.0 {
+        Ok(scale)
+    } else {
+        Err(format!("Scene scale must be positive, got {}", scale))
+    }
+}
+
+/// Samples per pixel fitting a total of `budget` camera rays, at least one
+fn samples_from_budget(budget: u64, width: usize, height: usize) -> u16 {
+    let pixels = (width * height).max(1) as u64;
+    (budget / pixels).max(1).min(u16::MAX as u64) as u16
+}
+
+/// Average `samples` colors drawn from `sample`, after drawing and
+/// discarding `warmup` of them.
+fn accumulate<F: FnMut() -> Color>(samples: usize, warmup: usize, mut sample: F) -> Color {
+    for _ in 0..warmup {
+        sample();
+    }
+    let mut color = image::colors::BLACK;
+    for _ in 0..samples {
+        color += sample();
+    }
+    &color / samples as f64
+}
+
+/// Material swatch: a unit sphere of `material` on a gray floor, lit by a
+/// key and a fill light
+fn studio_scene(material: Box<dyn Material>) -> Vec<Sphere> {
+    vec![
         Sphere::new(
             Point::new(0.0, -1000.0, 0.0),
             1000.0,
             Box::new(material::Lambertian::new(Color::new(0.5, 0.5, 0.5))),
         ),
+        Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, material),
+        Sphere::new(
+            Point::new(-4.0, 5.0, 4.0),
+            1.5,
+            Box::new(material::DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        ),
+        Sphere::new(
+            Point::new(5.0, 3.0, 2.0),
+            1.0,
+            Box::new(material::DiffuseLight::new(Color::new(2.0, 2.0, 2.0))),
+        ),
+    ]
+}
+
+/// Fixed camera framing the swatch of `studio_scene`
+fn studio_camera(aspect_ratio: f64) -> Camera {
+    let position = Point::new(0.0, 2.0, 8.0);
+    let look_at = Point::new(0.0, 1.0, 0.0);
+    let focus_dist = (look_at - position).length();
+    Camera::new(
+        position,
+        look_at,
+        Vector::new(0.0, 1.0, 0.0),
+        25.0,
+        aspect_ratio,
+        1.0,
+        0.0,
+        focus_dist,
+    )
+}
+
+/// Classic cover scene: three large spheres among small random ones
+fn random_scene<R: Rng>(rng: &mut R) -> Vec<Sphere> {
+    let mut spheres = vec![
+        Sphere::new(
+            Point::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Box::new(material::Lambertian::textured(Box::new(
+                texture::Checker::colors(
+                    Color::new(0.2, 0.3, 0.1),
+                    Color::new(0.9, 0.9, 0.9),
+                    10.0,
+                ),
+            ))),
+        ),
         Sphere::new(
             Point::new(0.0, 1.0, 0.0),
             1.0,
@@ -178,18 +1724,18 @@ fn main() {
     for a in -11..11 {
         for b in -11..11 {
             let center = Point::new(
-                a as f64 + 0.9 * random_unit(),
+                a as f64 + 0.9 * random_unit(rng),
                 0.2,
-                b as f64 + 0.9 * random_unit(),
+                b as f64 + 0.9 * random_unit(rng),
             );
             if (center - refp).length() > 0.9 {
-                let rd_material = random_unit();
+                let rd_material = random_unit(rng);
                 let material: Box<dyn material::Material> = if rd_material < 0.8 {
-                    let albedo = random_color() * random_color();
+                    let albedo = random_color(rng) * random_color(rng);
                     Box::new(material::Lambertian::new(albedo))
                 } else if rd_material < 0.95 {
-                    let albedo = random_color_ranged(0.5, 1.0);
-                    let fuzz = random_range(0.0, 0.5);
+                    let albedo = random_color_ranged(rng, 0.5, 1.0);
+                    let fuzz = random_range(rng, 0.0, 0.5);
                     Box::new(material::Metal::new(albedo, fuzz))
                 } else {
                     Box::new(material::Dielectric::new(1.5))
@@ -199,94 +1745,1021 @@ fn main() {
             }
         }
     }
-    let world = HittableVec::new(spheres);
-    // render
-    let mut settings = RenderSettings::default();
-    settings.aa_samples(100).ray_bounce_limit(50).gamma(2);
-    fill_image(&mut img, &settings, &camera, &world);
-    let file =
-        fs::File::create(&opt.output).expect(format!("Failed to open {}", opt.output).as_str());
-    let mut writer: ppm::PPMWriter<fs::File> = ppm::PPMWriter::new(file);
-    writer.write(&img).expect("Failed to write image");
-}
-
-fn random_in_hemisphere(normal: &Vector) -> Vector {
-    let random_unit = vec::random_unit_vector();
-    if vec::dot(&random_unit, normal) > 0.0 {
-        random_unit
-    } else {
-        -random_unit
-    }
+    spheres
 }
 
-fn ray_color(ray: &Ray, world: &HittableVec<Sphere>, depth: i16) -> Color {
-    // ray bounced too many times, no more light is gathered
-    if depth < 0 {
-        return image::colors::BLACK;
-    }
-    if let Some(hit) = world.hit_by(ray, 0.001, ray::T_INFINITY) {
-        let effect = hit.material.scatter(ray, &hit);
-        match effect.scattered {
-            None => return image::colors::BLACK,
-            Some(scattered) => return effect.attenuation * ray_color(&scattered, world, depth - 1),
-        }
-    }
-    let unit_dir = vec::unit(&ray.direction);
-    let t = 0.5 * (unit_dir.y + 1.0);
-    (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+fn random_range<R: Rng>(rng: &mut R, min: f64, max: f64) -> f64 {
+    rng.gen_range(min, max)
 }
 
-fn fill_image(
-    img: &mut image::Image,
-    settings: &RenderSettings,
-    camera: &Camera,
-    world: &HittableVec<Sphere>,
-) {
-    let range_rand = rand::distributions::Uniform::new(0.0, 1.0);
-    let mut rng = rand::thread_rng();
-    let samples = settings.antialiasing_samples;
-    for line in 0..img.height {
-        eprint!("\rLines remaining: {:3}", img.height - line);
-        io::stderr().flush().unwrap();
-        for col in 0..img.width {
-            let px = &mut img.data[line * img.width + col];
-            let mut color = image::colors::BLACK;
-            for _ in 0..samples {
-                let u = (col as f64 + range_rand.sample(&mut rng)) / (img.width as f64 - 1.0);
-                // render starts on top left
-                let v = (img.height as f64 - (line as f64 + range_rand.sample(&mut rng)))
-                    / (img.height as f64 - 1.0);
-                let ray = camera.ray(u, v);
-                color = color + ray_color(&ray, world, settings.ray_bounce_limit as i16);
-            }
-            // gamma correction
-            // gamma G means raising the color to the power 1/G
-            color = &color / samples as f64;
-            color.red = color.red.powf(settings.gamma);
-            color.green = color.green.powf(settings.gamma);
-            color.blue = color.blue.powf(settings.gamma);
-            color.clamp(0.0, 0.999);
-            *px = color;
-        }
-    }
+fn random_unit<R: Rng>(rng: &mut R) -> f64 {
+    random_range(rng, 0.0, 1.0)
 }
 
-fn random_range(min: f64, max: f64) -> f64 {
-    rand::thread_rng().gen_range(min, max)
+fn random_color<R: Rng>(rng: &mut R) -> Color {
+    Color::new(random_unit(rng), random_unit(rng), random_unit(rng))
 }
 
-fn random_unit() -> f64 {
-    random_range(0.0, 1.0)
+fn random_color_ranged<R: Rng>(rng: &mut R, min: f64, max: f64) -> Color {
+    Color::new(
+        random_range(rng, min, max),
+        random_range(rng, min, max),
+        random_range(rng, min, max),
+    )
 }
 
-fn random_color() -> Color {
-    Color::new(random_unit(), random_unit(), random_unit())
-}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::test::gray;
+    #[test]
+    fn warmup_samples_are_discarded() {
+        let mut drawn = 0;
+        let color = accumulate(3, 2, || {
+            let val = drawn as f64;
+            drawn += 1;
+            Color::new(val, val, val)
+        });
+        assert_eq!(5, drawn);
+        // samples 0 and 1 are discarded, 2, 3 and 4 are averaged
+        assert_eq!(3.0, color.red);
+    }
+    #[test]
+    fn warmup_keeps_estimate_unbiased() {
+        let color = accumulate(4, 10, || Color::new(0.25, 0.5, 0.75));
+        assert_eq!(0.25, color.red);
+        assert_eq!(0.5, color.green);
+        assert_eq!(0.75, color.blue);
+    }
+    #[test]
+    fn warmup_leaves_the_sampler_points_to_kept_samples() {
+        let world = HittableVec::new(Vec::<Sphere>::new());
+        let camera = studio_camera(1.0);
+        let render = |warmup: u16, samples: u16| {
+            let mut settings = RenderSettings::default();
+            settings
+                .aa_samples(samples)
+                .warmup_samples(warmup)
+                .sampler(sampler::Sampler::Stratified)
+                .seed(Some(5));
+            render_pixel(3, 2, (8.0, 8.0), &settings, &camera, &world)
+        };
+        // 3 + 4 samples would not fill a grid, the 4 kept ones still do
+        let (plain, warm) = (render(0, 4), render(3, 4));
+        assert_eq!(
+            (plain.red, plain.green, plain.blue),
+            (warm.red, warm.green, warm.blue)
+        );
+        // the total doesn't fit a u16
+        assert!(render(u16::MAX, u16::MAX).blue > 0.0);
+    }
+    #[test]
+    fn moved_camera_matches_fresh_camera() {
+        let vup = Vector::new(0.0, 1.0, 0.0);
+        let mut camera = Camera::new(
+            Point::new(13.0, 2.0, 3.0),
+            Point::new(0.0, 0.0, 0.0),
+            vup,
+            20.0,
+            1.5,
+            1.0,
+            0.0,
+            10.0,
+        );
+        let position = Point::new(-2.0, 4.0, 6.0);
+        let target = Point::new(1.0, 0.5, -1.0);
+        camera.set_position(position);
+        camera.set_look_at(target);
+        let fresh = Camera::new(position, target, vup, 20.0, 1.5, 1.0, 0.0, 10.0);
+        for &(t, s) in &[(0.0, 0.0), (0.5, 0.5), (0.25, 0.9), (1.0, 1.0)] {
+            let moved_ray = camera.ray(t, s);
+            let fresh_ray = fresh.ray(t, s);
+            assert_eq!(fresh_ray.origin, moved_ray.origin);
+            assert_eq!(fresh_ray.direction, moved_ray.direction);
+        }
+    }
+    #[test]
+    fn gray_override_ignores_surface_color() {
+        let world = HittableVec::new(vec![Sphere::new(
+            Point::new(0.0, 1.0, 0.0),
+            1.0,
+            Box::new(material::Lambertian::new(Color::new(0.9, 0.1, 0.1))),
+        )]);
+        let camera = studio_camera(1.0);
+        let render = |material_override: Option<MaterialOverride>| {
+            let mut settings = RenderSettings::default();
+            settings
+                .aa_samples(4)
+                .diffuse_bounces(4)
+                .seed(Some(2))
+                .background(Box::new(background::SolidBackground(Color::new(
+                    1.0, 1.0, 1.0,
+                ))))
+                .material_override(material_override);
+            let mut img = image::Image::new(8, 8);
+            fill_image(&mut img, &settings, &camera, &world, |_| true);
+            img
+        };
+        // the sphere fills the middle of the frame and shows its color
+        let red = render(None);
+        assert!(red.data[4 * 8 + 4].red > red.data[4 * 8 + 4].green);
+        let gray = render(Some("lambertian-gray".parse().unwrap()));
+        for px in &gray.data {
+            assert_eq!(px.red, px.green);
+            assert_eq!(px.red, px.blue);
+        }
+        assert!(gray.data[4 * 8 + 4].red < 1.0);
+    }
+    #[test]
+    fn normal_override_keeps_geometry() {
+        let world = HittableVec::new(vec![Sphere::new(
+            Point::new(0.0, 0.0, -1.0),
+            0.5,
+            Box::new(material::Lambertian::new(Color::new(0.9, 0.1, 0.1))),
+        )]);
+        let mut normal = RenderSettings::default();
+        normal.material_override(Some(MaterialOverride::Normal));
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let hitting = Ray::new(origin, Vector::new(0.0, 0.0, -1.0));
+        let color = ray_color(&hitting, &world, normal.bounces, &normal);
+        assert_eq!(0.5, color.red);
+        assert_eq!(0.5, color.green);
+        assert_eq!(1.0, color.blue);
+        let missing = Ray::new(origin, Vector::new(0.0, 1.0, 0.0));
+        let sky = ray_color(&missing, &world, normal.bounces, &normal);
+        let settings = RenderSettings::default();
+        let expected = ray_color(&missing, &world, settings.bounces, &settings);
+        assert_eq!(expected.red, sky.red);
+        assert_eq!(expected.green, sky.green);
+        assert_eq!(expected.blue, sky.blue);
+    }
+    #[test]
+    fn unknown_material_override_is_rejected() {
+        assert!("plastic".parse::<MaterialOverride>().is_err());
+    }
+    #[test]
+    fn primary_visibility_lights_facing_side() {
+        let world = HittableVec::new(vec![Sphere::new(
+            Point::new(0.0, 0.0, -1.0),
+            0.5,
+            Box::new(material::Lambertian::new(Color::new(0.8, 0.8, 0.8))),
+        )]);
+        let camera = Camera::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let mut img = image::Image::new(11, 11);
+        fill_image_primary_visibility(&mut img, &RenderSettings::default(), &camera, &world);
+        let center = img.data[5 * 11 + 5];
+        let side = img.data[5 * 11 + 7];
+        assert!(center.red > side.red);
+        assert!(side.red > 0.0);
+        let corner = img.data[0];
+        let mut sky = RenderSettings::default()
+            .background
+            .sample(&camera.ray(0.0, 1.0));
+        correct(&mut sky, &RenderSettings::default());
+        assert_eq!(sky.red, corner.red);
+        assert_eq!(sky.green, corner.green);
+        assert_eq!(sky.blue, corner.blue);
+    }
+    #[test]
+    fn region_matches_full_render() {
+        // camera inside a large sphere, shaded by its normals: smooth and
+        // deterministic apart from the jitter inside each pixel
+        let world = HittableVec::new(vec![Sphere::new(Point::new(0.0, 0.0, 0.0), 100.0, gray())]);
+        let camera = Camera::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let mut settings = RenderSettings::default();
+        settings
+            .aa_samples(256)
+            .material_override(Some(MaterialOverride::Normal));
+        let mut full = image::Image::new(16, 16);
+        fill_image(&mut full, &settings, &camera, &world, |_| true);
+        let region: Region = "4,6,12,14".parse().unwrap();
+        let mut roi = image::Image::new(region.width(), region.height());
+        render_region(&mut roi, 16, 16, &region, &settings, &camera, &world);
+        for line in 0..roi.height {
+            for col in 0..roi.width {
+                let expected = full.data[(line + 6) * 16 + col + 4];
+                let px = roi.data[line * roi.width + col];
+                assert!((expected.red - px.red).abs() < 0.02);
+                assert!((expected.green - px.green).abs() < 0.02);
+                assert!((expected.blue - px.blue).abs() < 0.02);
+            }
+        }
+    }
+    #[test]
+    fn invalid_region_is_rejected() {
+        assert!("1,2,3".parse::<Region>().is_err());
+        assert!("4,0,2,2".parse::<Region>().is_err());
+        assert!("a,0,2,2".parse::<Region>().is_err());
+        let region: Region = "4,6,12,14".parse().unwrap();
+        assert!(region.check_within(12, 14).is_ok());
+        assert_eq!(
+            Err("Region of interest 4,6,12,14 exceeds the 12x10 image".to_string()),
+            region.check_within(12, 10)
+        );
+    }
+    #[test]
+    fn no_gamma_keeps_linear_colors() {
+        let mid_gray = Color::new(0.5, 0.5, 0.5);
+        let mut settings = RenderSettings::default();
+        let mut linear = mid_gray;
+        correct(&mut linear, settings.gamma(1.0));
+        assert_eq!(0.5, linear.red);
+        assert_eq!(0.5, linear.green);
+        assert_eq!(0.5, linear.blue);
+        let mut corrected = mid_gray;
+        correct(&mut corrected, settings.gamma(2.0));
+        assert_eq!(image::Transfer::Gamma(0.5), settings.transfer);
+        assert!((corrected.red - 0.5f64.sqrt()).abs() < 1e-12);
+        let mut corrected = mid_gray;
+        correct(&mut corrected, settings.gamma(2.2));
+        assert!((corrected.red - 0.5f64.powf(1.0 / 2.2)).abs() < 1e-12);
+    }
+    #[test]
+    #[should_panic]
+    fn zero_gamma_is_rejected() {
+        RenderSettings::default().gamma(0.0);
+    }
+    #[test]
+    fn aovs_from_first_hit() {
+        let world = HittableVec::new(vec![Sphere::new(
+            Point::new(0.0, 0.0, -1.0),
+            0.5,
+            Box::new(material::Lambertian::new(Color::new(0.8, 0.3, 0.1))),
+        )]);
+        let camera = Camera::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let aovs = [Aov::Albedo, Aov::Normal, Aov::Depth];
+        let images = fill_aovs(&aovs, 5, 5, &RenderSettings::default(), &camera, &world);
+        assert_eq!(3, images.len());
+        let center = 2 * 5 + 2;
+        let albedo = images[0].data[center];
+        assert_eq!((0.8, 0.3, 0.1), (albedo.red, albedo.green, albedo.blue));
+        let normal = images[1].data[center];
+        assert_eq!((0.5, 0.5, 1.0), (normal.red, normal.green, normal.blue));
+        assert!((images[2].data[center].red - 0.5).abs() < 1e-9);
+        // corners see the sky
+        assert_eq!(0.0, images[2].data[0].red);
+    }
+    #[test]
+    fn aov_files_are_named_after_output() {
+        assert_eq!("out.albedo.ppm", aov_path("out.ppm", Aov::Albedo));
+        assert_eq!("dir/out.depth.ppm", aov_path("dir/out.ppm", Aov::Depth));
+        assert_eq!("out.normal", aov_path("out", Aov::Normal));
+    }
+    #[test]
+    fn hdr_output_keeps_colors_unclamped() {
+        let mut settings = RenderSettings::default();
+        settings.gamma(2.0).hdr_output(true);
+        let mut color = Color::new(2.5, 0.25, 0.0);
+        correct(&mut color, &settings);
+        assert_eq!((2.5, 0.25, 0.0), (color.red, color.green, color.blue));
+        assert!(has_extension("render.PFM", "pfm"));
+        assert!(!has_extension("render.ppm", "pfm"));
+        assert!(is_hdr_format("render.hdr"));
+        assert!(!is_hdr_format("render.jpg"));
+    }
+    #[test]
+    fn scaled_scene_renders_the_same() {
+        // mirrors and sky only, no randomness apart from the lens which is closed
+        let spheres = || {
+            HittableVec::new(vec![
+                Sphere::new(
+                    Point::new(0.0, -100.5, -1.0),
+                    100.0,
+                    Box::new(material::Metal::new(Color::new(0.8, 0.8, 0.8), 0.0)),
+                ),
+                Sphere::new(
+                    Point::new(0.0, 0.0, -1.0),
+                    0.5,
+                    Box::new(material::Metal::new(Color::new(0.7, 0.3, 0.3), 0.0)),
+                ),
+            ])
+        };
+        let camera = |scale: f64| {
+            Camera::new(
+                scale * Point::new(0.0, 0.5, 1.0),
+                scale * Point::new(0.0, 0.0, -1.0),
+                Vector::new(0.0, 1.0, 0.0),
+                60.0,
+                1.0,
+                1.0,
+                0.0,
+                scale * 2.0,
+            )
+        };
+        let (camera, scaled_camera) = (camera(1.0), camera(10.0));
+        let world = Scale::new(spheres(), 1.0);
+        let scaled_world = Scale::new(spheres(), 10.0);
+        let mut settings = RenderSettings::default();
+        settings.glass_bounces(10).diffuse_bounces(10);
+        let mut scaled_settings = RenderSettings::default();
+        scaled_settings
+            .glass_bounces(10)
+            .diffuse_bounces(10)
+            .ray_epsilon(0.01);
+        for line in 0..8 {
+            for col in 0..8 {
+                let (u, v) = (col as f64 / 7.0, line as f64 / 7.0);
+                let expected = ray_color(&camera.ray(u, v), &world, settings.bounces, &settings);
+                let ray = scaled_camera.ray(u, v);
+                let color = ray_color(
+                    &ray,
+                    &scaled_world,
+                    scaled_settings.bounces,
+                    &scaled_settings,
+                );
+                assert!((expected.red - color.red).abs() < 1e-6);
+                assert!((expected.green - color.green).abs() < 1e-6);
+                assert!((expected.blue - color.blue).abs() < 1e-6);
+            }
+        }
+    }
+    #[derive(Debug)]
+    struct CountingMaterial {
+        category: BounceCategory,
+        scatters: std::sync::Arc<std::sync::atomic::AtomicU32>,
+    }
+    impl Material for CountingMaterial {
+        fn scatter(&self, _ray: &Ray, hit: &ray::HitRecord) -> material::MaterialEffect {
+            self.scatters
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            // toward the opposite side of the enclosing sphere
+            let ray = Ray::new(hit.point, hit.normal);
+            material::MaterialEffect::new(Color::new(1.0, 1.0, 1.0), ray)
+        }
+        fn albedo(&self, _hit: &ray::HitRecord) -> Color {
+            Color::new(1.0, 1.0, 1.0)
+        }
+        fn bounce_category(&self) -> BounceCategory {
+            self.category
+        }
+    }
+    #[test]
+    fn glass_paths_bounce_more_than_diffuse_paths() {
+        let mut settings = RenderSettings::default();
+        settings.glass_bounces(12).diffuse_bounces(3);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let path_length = |category| {
+            // the path bounces inside the sphere until its budget runs out
+            let scatters = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
+            let material = CountingMaterial {
+                category,
+                scatters: scatters.clone(),
+            };
+            let world = HittableVec::new(vec![Sphere::new(
+                Point::new(0.0, 0.0, 0.0),
+                1.0,
+                Box::new(material),
+            )]);
+            let color = ray_color(&ray, &world, settings.bounces, &settings);
+            assert_eq!(0.0, color.red);
+            scatters.load(std::sync::atomic::Ordering::SeqCst)
+        };
+        let glass = path_length(BounceCategory::Glass);
+        let diffuse = path_length(BounceCategory::Diffuse);
+        assert_eq!(12, glass);
+        assert_eq!(3, diffuse);
+        assert!(glass > diffuse);
+    }
+    #[test]
+    fn bounce_budget_is_spent_per_category() {
+        let budget = BounceBudget {
+            glass: 1,
+            diffuse: 2,
+        };
+        let budget = budget.spend(BounceCategory::Glass).unwrap();
+        assert_eq!((0, 2), (budget.glass, budget.diffuse));
+        assert!(budget.spend(BounceCategory::Glass).is_none());
+        let budget = budget.spend(BounceCategory::Diffuse).unwrap();
+        assert_eq!((0, 1), (budget.glass, budget.diffuse));
+    }
+    // image coordinates (u, v) where `p` is seen through a pinhole camera
+    fn project(camera: &Camera, p: &Point) -> (f64, f64) {
+        let d = p - camera.position;
+        let dist = camera.focal * camera.focus_dist / vec::dot(&d, &-camera.w);
+        let rel = camera.position + dist * d - camera.lower_left_corner;
+        (
+            vec::dot(&rel, &camera.horizontal) / camera.horizontal.length_squared(),
+            vec::dot(&rel, &camera.vertical) / camera.vertical.length_squared(),
+        )
+    }
+    #[test]
+    fn lens_shift_moves_framing_without_tilting() {
+        let look_at = Point::new(0.0, 1.0, 0.0);
+        let mut camera = Camera::new(
+            Point::new(0.0, 1.0, 10.0),
+            look_at,
+            Vector::new(0.0, 1.0, 0.0),
+            40.0,
+            1.5,
+            1.0,
+            0.0,
+            10.0,
+        );
+        let (u, v) = project(&camera, &look_at);
+        assert!((u - 0.5).abs() < 1e-9 && (v - 0.5).abs() < 1e-9);
+        camera.set_lens_shift(Vector::new(0.0, 0.2, 0.0));
+        // the view moved up, the target appears lower in the frame
+        let (u, v) = project(&camera, &look_at);
+        assert!((u - 0.5).abs() < 1e-9);
+        assert!((v - 0.3).abs() < 1e-9);
+        // a vertical line off center still projects to a single column
+        let bottom = project(&camera, &Point::new(2.0, 0.0, -3.0));
+        let top = project(&camera, &Point::new(2.0, 5.0, -3.0));
+        assert!((bottom.0 - top.0).abs() < 1e-9);
+        assert!(top.1 > bottom.1);
+    }
+    #[test]
+    fn over_bright_reflector_is_clipped() {
+        // a mirror reflecting 4 times the sky above a dark ground
+        let world = HittableVec::new(vec![
+            Sphere::new(
+                Point::new(0.0, -100.5, -1.0),
+                100.0,
+                Box::new(material::Lambertian::new(Color::new(0.05, 0.05, 0.05))),
+            ),
+            Sphere::new(
+                Point::new(0.0, 0.0, -1.0),
+                0.5,
+                Box::new(material::Metal::new(Color::new(4.0, 4.0, 4.0), 0.0)),
+            ),
+        ]);
+        let camera = Camera::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let mut settings = RenderSettings::default();
+        settings
+            .aa_samples(4)
+            .glass_bounces(5)
+            .diffuse_bounces(5)
+            .gamma(2.0)
+            .hdr_output(true);
+        let mut img = image::Image::new(11, 11);
+        fill_image(&mut img, &settings, &camera, &world, |_| true);
+        let clipped = img.clipped_mask(1.0);
+        assert!(clipped[5 * 11 + 5]);
+        // the bottom line only sees the ground
+        assert!(clipped[10 * 11..].iter().all(|&c| !c));
+        let bottom = img.data[10 * 11];
+        settings.hdr_output(false);
+        highlight_clipped(&mut img, &settings);
+        let center = img.data[5 * 11 + 5];
+        assert_eq!((1.0, 0.0, 1.0), (center.red, center.green, center.blue));
+        assert!((img.data[10 * 11].red - bottom.red.sqrt()).abs() < 1e-12);
+    }
+    #[test]
+    fn seeded_scenes_are_identical() {
+        let first = random_scene(&mut StdRng::seed_from_u64(42));
+        let second = random_scene(&mut StdRng::seed_from_u64(42));
+        assert_eq!(first.len(), second.len());
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a.center, b.center);
+            assert_eq!(a.radius, b.radius);
+            assert_eq!(format!("{:?}", a.material), format!("{:?}", b.material));
+        }
+        let other = random_scene(&mut StdRng::seed_from_u64(43));
+        assert!(first.iter().zip(&other).any(|(a, b)| a.center != b.center));
+    }
+    #[test]
+    fn ray_budget_sets_samples_per_pixel() {
+        assert_eq!(1, samples_from_budget(100, 10, 10));
+        assert_eq!(2, samples_from_budget(200, 10, 10));
+        assert_eq!(2, samples_from_budget(299, 10, 10));
+        assert_eq!(1, samples_from_budget(10, 10, 10));
+    }
+    #[test]
+    fn tile_size_needs_tiles() {
+        let parse =
+            |args: &[&str]| Options::from_iter_safe(["ray"].iter().chain(args).chain(&["out.png"]));
+        assert_eq!(None, parse(&[]).unwrap().tile_size);
+        assert_eq!(
+            Some(16),
+            parse(&["--tiled", "--tile-size", "16"]).unwrap().tile_size
+        );
+        assert!(parse(&["--tile-size", "16"]).is_err());
+    }
+    #[test]
+    fn denoise_iterations_are_bounded() {
+        let parse =
+            |args: &[&str]| Options::from_iter_safe(["ray"].iter().chain(args).chain(&["out.png"]));
+        assert_eq!(5, parse(&[]).unwrap().denoise_iterations);
+        assert_eq!(
+            10,
+            parse(&["--denoise-iterations", "10"])
+                .unwrap()
+                .denoise_iterations
+        );
+        assert!(parse(&["--denoise-iterations", "0"]).is_err());
+        assert!(parse(&["--denoise-iterations", "64"]).is_err());
+        assert!(parse(&["--denoise-iterations", "five"]).is_err());
+    }
+    #[test]
+    fn scene_scale_is_positive_and_finite() {
+        let parse =
+            |args: &[&str]| Options::from_iter_safe(["ray"].iter().chain(args).chain(&["out.png"]));
+        assert_eq!(1.0, parse(&[]).unwrap().scene_scale);
+        assert_eq!(0.5, parse(&["--scene-scale", "0.5"]).unwrap().scene_scale);
+        for bad in &["0", "-2", "inf", "NaN"] {
+            let arg = format!("--scene-scale={}", bad);
+            assert!(parse(&[&arg]).is_err(), "{}", bad);
+        }
+    }
+    #[test]
+    fn auto_levels_percentiles_are_ordered() {
+        let parse = |args: &[&str]| {
+            let opt =
+                Options::from_iter_safe(["ray"].iter().chain(args).chain(&["out.png"])).unwrap();
+            auto_levels_option(&opt)
+        };
+        assert_eq!(Ok((0.01, 0.99)), parse(&["--auto-levels"]));
+        assert_eq!(
+            Ok((0.0, 0.5)),
+            parse(&[
+                "--auto-levels",
+                "--auto-levels-low",
+                "0",
+                "--auto-levels-high",
+                "0.5"
+            ])
+        );
+        assert!(parse(&["--auto-levels", "--auto-levels-low", "0.99"]).is_err());
+        assert!(parse(&["--auto-levels", "--auto-levels-high", "1.5"]).is_err());
+        assert!(parse(&["--auto-levels", "--auto-levels-low=-0.1"]).is_err());
+        assert!(parse(&["--auto-levels", "--auto-levels-high", "NaN"]).is_err());
+        assert!(Options::from_iter_safe(&["ray", "--auto-levels-low", "0.1", "out.png"]).is_err());
+    }
+    #[test]
+    fn upscale_factor_is_positive() {
+        let parse =
+            |args: &[&str]| Options::from_iter_safe(["ray"].iter().chain(args).chain(&["out.png"]));
+        assert_eq!(None, parse(&[]).unwrap().upscale);
+        assert_eq!(Some(3), parse(&["--upscale", "3"]).unwrap().upscale);
+        assert!(parse(&["--upscale", "0"]).is_err());
+        assert!(parse(&["--upscale", "-2"]).is_err());
+    }
+    #[test]
+    fn height_sets_the_aspect_ratio() {
+        let opt = Options::from_iter(&["ray", "--width", "400", "--height", "400", "out.png"]);
+        let (width, height, aspect_ratio) = image_size(opt.width, opt.height);
+        assert_eq!((400, 400, 1.0), (width, height, aspect_ratio));
+        let camera = studio_camera(aspect_ratio);
+        assert_eq!(1.0, camera.viewport.width / camera.viewport.height);
+        // 3:2 by default
+        assert_eq!((1200, 800, 1.5), image_size(1200, None));
+    }
+    #[test]
+    fn quality_options_set_render_settings() {
+        let parse = |args: &[&str]| {
+            let opt = Options::from_iter(["ray"].iter().chain(args).chain(&["out.png"]));
+            let mut settings = RenderSettings::default();
+            apply_quality_options(&opt, 10, 10, None, &mut settings).map(|_| settings)
+        };
+        let settings = parse(&["--samples", "16", "--bounces", "8", "--gamma", "2"]).unwrap();
+        assert_eq!(16, settings.antialiasing_samples);
+        assert_eq!((8, 8), (settings.bounces.glass, settings.bounces.diffuse));
+        assert_eq!(image::Transfer::Gamma(0.5), settings.transfer);
+        let defaults = parse(&["--glass-bounces", "4"]).unwrap();
+        assert_eq!(100, defaults.antialiasing_samples);
+        assert_eq!((4, 50), (defaults.bounces.glass, defaults.bounces.diffuse));
+        assert_eq!(image::Transfer::Gamma(1.0 / 2.2), defaults.transfer);
+        assert!(parse(&["--samples", "0"]).is_err());
+        assert!(parse(&["--gamma", "0"]).is_err());
+        assert!(parse(&["--gamma=-1.5"]).is_err());
+        // 1000 rays over 10x10 pixels
+        let budget = parse(&["--ray-budget", "1000"]).unwrap();
+        assert_eq!(10, budget.antialiasing_samples);
+        let linear = parse(&["--no-gamma"]).unwrap();
+        assert_eq!(image::Transfer::Gamma(1.0), linear.transfer);
+        let srgb = parse(&["--srgb"]).unwrap();
+        assert_eq!(image::Transfer::Srgb, srgb.transfer);
+        let conflict = |args: &[&str]| {
+            Options::from_iter_safe(["ray"].iter().chain(args).chain(&["out.png"])).is_err()
+        };
+        assert!(conflict(&["--samples", "4", "--ray-budget", "1000"]));
+        assert!(conflict(&["--gamma", "2", "--srgb"]));
+    }
+    #[test]
+    fn explicit_options_override_the_scene() {
+        let scene: scene::SceneSettings =
+            serde_json::from_str(r#"{"samples": 16, "diffuse_bounces": 8}"#).unwrap();
+        let parse = |args: &[&str]| {
+            let opt = Options::from_iter(["ray"].iter().chain(args).chain(&["out.png"]));
+            let mut settings = RenderSettings::default();
+            apply_quality_options(&opt, 10, 10, Some(&scene), &mut settings).unwrap();
+            settings
+        };
+        let settings = parse(&[]);
+        assert_eq!(16, settings.antialiasing_samples);
+        assert_eq!((50, 8), (settings.bounces.glass, settings.bounces.diffuse));
+        let settings = parse(&["--samples", "4", "--bounces", "3"]);
+        assert_eq!(4, settings.antialiasing_samples);
+        assert_eq!((3, 3), (settings.bounces.glass, settings.bounces.diffuse));
+        let settings = parse(&["--diffuse-bounces", "2"]);
+        assert_eq!((50, 2), (settings.bounces.glass, settings.bounces.diffuse));
+    }
+    #[test]
+    fn whitepoint_or_tone_map_option() {
+        let parse = |args: &[&str]| {
+            Options::from_iter_safe(["ray"].iter().chain(args).chain(&["out.png"]))
+                .map(|opt| tone_map_option(&opt))
+        };
+        assert_eq!(image::ToneMap::Clamp, parse(&[]).unwrap());
+        assert_eq!(
+            image::ToneMap::ReinhardExtended { white: 4.0 },
+            parse(&["--whitepoint", "4"]).unwrap()
+        );
+        assert_eq!(
+            image::ToneMap::AcesFilmic,
+            parse(&["--tone-map", "aces"]).unwrap()
+        );
+        assert!(parse(&["--whitepoint", "4", "--tone-map", "aces"]).is_err());
+    }
+    // always scatters toward `direction`, halving the light
+    #[derive(Debug)]
+    struct Redirect {
+        direction: Vector,
+    }
+    impl Material for Redirect {
+        fn scatter(&self, _ray: &Ray, hit: &ray::HitRecord) -> material::MaterialEffect {
+            let ray = Ray::new(hit.point, self.direction);
+            material::MaterialEffect::new(Color::new(0.5, 0.5, 0.5), ray)
+        }
+        fn albedo(&self, _hit: &ray::HitRecord) -> Color {
+            Color::new(0.5, 0.5, 0.5)
+        }
+    }
+    #[test]
+    fn indirect_only_keeps_bounced_light() {
+        let sphere = |center: Point, direction: Vector| {
+            Sphere::new(center, 0.5, Box::new(Redirect { direction }))
+        };
+        let mut full = RenderSettings::default();
+        full.diffuse_bounces(5);
+        let mut indirect = RenderSettings::default();
+        indirect.diffuse_bounces(5).indirect_only(true);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        // the first surface sends light straight to the sky
+        let direct = HittableVec::new(vec![sphere(
+            Point::new(0.0, 0.0, -2.0),
+            Vector::new(0.0, 1.0, 1.0),
+        )]);
+        let lit = ray_color(&ray, &direct, full.bounces, &full);
+        let gi = ray_color(&ray, &direct, indirect.bounces, &indirect);
+        assert!(lit.red > 0.0);
+        assert!(gi.red < lit.red);
+        // the first surface only sees a second one, which sees the sky
+        let bounced = HittableVec::new(vec![
+            sphere(Point::new(0.0, 0.0, -2.0), Vector::new(1.0, 0.0, 1.0)),
+            sphere(Point::new(3.0, 0.0, 1.5), Vector::new(-1.0, 1.0, 0.0)),
+        ]);
+        let lit = ray_color(&ray, &bounced, full.bounces, &full);
+        let gi = ray_color(&ray, &bounced, indirect.bounces, &indirect);
+        assert!(lit.red > 0.0);
+        assert_eq!((lit.red, lit.green, lit.blue), (gi.red, gi.green, gi.blue));
+        // the sky seen by the camera is direct light too
+        let sky = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(
+            0.0,
+            ray_color(&sky, &bounced, indirect.bounces, &indirect).red
+        );
+    }
+    #[test]
+    fn framed_scene_is_in_view() {
+        let world = HittableVec::new(vec![
+            Sphere::new(Point::new(-50.0, 0.0, 0.0), 1.0, gray()),
+            Sphere::new(Point::new(50.0, 10.0, -20.0), 2.0, gray()),
+        ]);
+        let camera = Camera::frame_scene(&world, 1.5, 0.1).unwrap();
+        assert!(camera
+            .look_at
+            .approx_eq(&world.bounding_box().center(), 1e-9));
+        // every corner of the spheres bounding boxes is in view
+        let bbox = world.bounding_box();
+        for &x in &[bbox.x.min, bbox.x.max] {
+            for &y in &[bbox.y.min, bbox.y.max] {
+                for &z in &[bbox.z.min, bbox.z.max] {
+                    let (u, v) = project(&camera, &Point::new(x, y, z));
+                    assert!((0.0..=1.0).contains(&u), "u = {}", u);
+                    assert!((0.0..=1.0).contains(&v), "v = {}", v);
+                }
+            }
+        }
+    }
+    #[test]
+    fn planes_are_left_out_of_the_frame() {
+        let floor = || {
+            Box::new(plane::Plane::new(
+                Point::new(0.0, -1.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+                gray(),
+            )) as Box<dyn Hittable>
+        };
+        let world: World = HittableVec::new(vec![
+            floor(),
+            Box::new(Sphere::new(Point::new(4.0, 0.0, 0.0), 1.0, gray())),
+        ]);
+        let camera = Camera::frame_scene(&Scale::new(world, 2.0), 1.5, 0.1).unwrap();
+        assert!(camera.look_at.approx_eq(&Point::new(8.0, 0.0, 0.0), 1e-9));
+        assert!(camera.position.x.is_finite());
+        let floor_only: World = HittableVec::new(vec![floor()]);
+        assert!(Camera::frame_scene(&floor_only, 1.5, 0.1).is_none());
+    }
+    #[test]
+    fn material_preview_shows_the_swatch() {
+        let render = |albedo: Color| {
+            let world = HittableVec::new(studio_scene(Box::new(material::Lambertian::new(albedo))));
+            let camera = studio_camera(1.5);
+            let mut settings = RenderSettings::default();
+            settings.aa_samples(64).diffuse_bounces(8);
+            let mut img = image::Image::new(15, 10);
+            fill_image(&mut img, &settings, &camera, &world, |_| true);
+            let mut sky = settings.background.sample(&camera.ray(0.5, 0.5));
+            correct(&mut sky, &settings);
+            (img.data[5 * 15 + 7], sky)
+        };
+        let (red, sky) = render(Color::new(0.8, 0.1, 0.1));
+        assert!((red.red - sky.red).abs() > 0.1 || (red.blue - sky.blue).abs() > 0.1);
+        assert!(red.red > red.blue + 0.2);
+        let (blue, _) = render(Color::new(0.1, 0.1, 0.8));
+        assert!(blue.blue > blue.red + 0.2);
+    }
+    #[test]
+    fn light_emission_is_gathered() {
+        let world = HittableVec::new(vec![Sphere::new(
+            Point::new(0.0, 0.0, -1.0),
+            0.5,
+            Box::new(material::DiffuseLight::new(Color::new(3.0, 2.0, 1.0))),
+        )]);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let settings = RenderSettings::default();
+        let color = ray_color(&ray, &world, settings.bounces, &settings);
+        assert_eq!((3.0, 2.0, 1.0), (color.red, color.green, color.blue));
+    }
+    #[test]
+    fn parallel_bands_cover_every_line() {
+        let world = HittableVec::new(Vec::<Sphere>::new());
+        let camera = studio_camera(1.0);
+        let mut settings = RenderSettings::default();
+        settings.aa_samples(1);
+        // last band is incomplete
+        let height = 2 * 4 * rayon::current_num_threads() + 3;
+        let mut img = image::Image::new(5, height);
+        let mut bands = 0;
+        assert!(fill_image(&mut img, &settings, &camera, &world, |_| {
+            bands += 1;
+            true
+        }));
+        assert_eq!(3, bands);
+        assert!(img.data.iter().all(|px| px.red > 0.0));
+        // sky fades from blue at the top to white at the bottom
+        assert!(img.data[0].red < img.data[(height - 1) * 5].red);
+    }
+    #[test]
+    fn interrupted_render_saves_partial_image() {
+        // nothing but sky, every rendered pixel is lit
+        let world = HittableVec::new(Vec::<Sphere>::new());
+        let camera = studio_camera(4.0 / 3.0);
+        let mut settings = RenderSettings::default();
+        settings.aa_samples(1);
+        let mut checkpoint = checkpoint::Checkpoint::new(None);
+        let path = std::env::temp_dir().join(format!("raytracer-{}-ckpt.ppm", std::process::id()));
+        let path = path.to_str().unwrap();
+        // interrupted after the first of two bands
+        let band = 4 * rayon::current_num_threads();
+        let mut img = image::Image::new(4, 2 * band);
+        let finished = fill_image(&mut img, &settings, &camera, &world, |partial| {
+            checkpoint.interrupt();
+            handle_checkpoint(checkpoint.poll(), path, partial)
+        });
+        assert!(!finished);
+        let data = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let header = format!("P6\n4 {}\n255\n", 2 * band);
+        assert_eq!(header.as_bytes(), &data[..header.len()]);
+        let values = &data[header.len()..];
+        assert_eq!(2 * band * 4 * 3, values.len());
+        assert!(values[..band * 4 * 3].iter().all(|&v| v > 0));
+        assert!(values[band * 4 * 3..].iter().all(|&v| v == 0));
+    }
+    #[test]
+    fn camera_rays_are_cast_while_the_shutter_is_open() {
+        let mut camera = studio_camera(1.0);
+        assert_eq!(0.0, camera.ray(0.5, 0.5).time);
+        camera.set_shutter(1.0, 1.5);
+        for _ in 0..100 {
+            let time = camera.ray(0.5, 0.5).time;
+            assert!((1.0..1.5).contains(&time));
+            let time = camera.ray_with_lens(0.5, 0.5, (0.0, 0.0)).time;
+            assert!((1.0..1.5).contains(&time));
+        }
+    }
+    #[test]
+    fn seeded_renders_are_reproducible() {
+        let world = HittableVec::new(studio_scene(Box::new(material::Lambertian::new(
+            Color::new(0.5, 0.5, 0.5),
+        ))));
+        let camera = studio_camera(1.0);
+        let render = |seed: u64| {
+            let mut settings = RenderSettings::default();
+            settings.aa_samples(2).diffuse_bounces(4).seed(Some(seed));
+            let mut img = image::Image::new(8, 8);
+            fill_image(&mut img, &settings, &camera, &world, |_| true);
+            img.data.iter().map(|c| c.to_rgb8()).collect::<Vec<_>>()
+        };
+        let first = render(7);
+        assert_eq!(first, render(7));
+        assert_ne!(first, render(8));
+    }
+    #[test]
+    fn tiled_render_matches_single_threaded_one() {
+        let world = HittableVec::new(studio_scene(Box::new(material::Lambertian::new(
+            Color::new(0.5, 0.5, 0.5),
+        ))));
+        let camera = studio_camera(1.5);
+        let mut settings = RenderSettings::default();
+        settings.aa_samples(2).diffuse_bounces(4).seed(Some(3));
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let mut lines = image::Image::new(11, 7);
+        pool.install(|| fill_image(&mut lines, &settings, &camera, &world, |_| true));
+        // tiles cut on both edges
+        let mut tiles = image::Image::new(11, 7);
+        fill_image_tiled(&mut tiles, &settings, &camera, &world, 4);
+        let rgb = |img: &image::Image| {
+            img.data
+                .iter()
+                .map(|c| (c.red, c.green, c.blue))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rgb(&lines), rgb(&tiles));
+        assert!(tiles.data.iter().all(|c| c.red > 0.0));
+    }
+    #[test]
+    fn shadowed_points_get_no_direct_light() {
+        let world: World = HittableVec::new(vec![
+            Box::new(plane::Plane::new(
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+                gray(),
+            )),
+            // right between the origin and the light
+            Box::new(Sphere::new(Point::new(2.0, 1.0, 0.0), 0.5, gray())),
+        ]);
+        let mut settings = RenderSettings::default();
+        settings.lights(vec![light::PointLight::new(
+            Point::new(4.0, 2.0, 0.0),
+            Color::new(10.0, 10.0, 10.0),
+        )]);
+        let direct = |x: f64| {
+            let ray = Ray::new(Point::new(x, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+            let hit = world.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+            direct_light(&ray, &hit, hit.material.as_ref(), &world, &settings)
+        };
+        // 4 units back along x and 2 up, so a cosine of 2 / sqrt(20) over a
+        // squared distance of 20
+        let lit = direct(8.0);
+        let expected = 10.0 * (0.5 / std::f64::consts::PI) * (2.0 / 20f64.sqrt()) / 20.0;
+        assert!((lit.red - expected).abs() < 1e-12);
+        let shadowed = direct(0.0);
+        assert_eq!(0.0, shadowed.red);
+    }
+    // former recursive version of ray_color
+    fn ray_color_recursive(
+        ray: &Ray,
+        world: &dyn Hittable,
+        bounces: BounceBudget,
+        settings: &RenderSettings,
+    ) -> Color {
+        if let Some(hit) = world.hit(
+            ray,
+            Interval::new(settings.ray_epsilon, Interval::UNIVERSE.max),
+        ) {
+            let material: &dyn Material = match &settings.material_override {
+                None => hit.material.as_ref(),
+                Some(MaterialOverride::Material(material)) => material.as_ref(),
+                Some(MaterialOverride::Normal) => {
+                    let n = hit.normal;
+                    return 0.5 * Color::new(n.x + 1.0, n.y + 1.0, n.z + 1.0);
+                }
+            };
+            // lights seen directly or lighting the first surface are direct light
+            let emitted = if settings.indirect_only && settings.bounces.spent(&bounces) < 2 {
+                image::colors::BLACK
+            } else {
+                material.emitted()
+            };
+            // point lights lighting the first surface are direct light
+            let lit = settings.direct_lighting
+                && !(settings.indirect_only && settings.bounces.spent(&bounces) < 1);
+            // ray bounced too many times on this kind of material,
+            // no more light is gathered
+            let bounces = match bounces.spend(material.bounce_category()) {
+                Some(bounces) => bounces,
+                None => return emitted,
+            };
+            let effect = material.scatter(ray, &hit);
+            let direct = if lit && !effect.is_specular {
+                direct_light(ray, &hit, material, world, settings)
+            } else {
+                image::colors::BLACK
+            };
+            match effect.scattered {
+                None => return emitted + direct,
+                Some(scattered) => {
+                    return emitted
+                        + direct
+                        + effect.attenuation
+                            * ray_color_recursive(&scattered, world, bounces, settings)
+                }
+            }
+        }
+        // the sky seen directly or lighting the first surface is direct light
+        if settings.indirect_only && settings.bounces.spent(&bounces) < 2 {
+            return image::colors::BLACK;
+        }
+        settings.background.sample(ray)
+    }
 
-fn random_color_ranged(min: f64, max: f64) -> Color {
-    Color::new(
-        random_range(min, max),
-        random_range(min, max),
-        random_range(min, max),
-    )
+    #[test]
+    fn iterative_ray_color_matches_recursion() {
+        let mut world = studio_scene(Box::new(material::Dielectric::new(1.5)));
+        world.push(Sphere::new(
+            Point::new(-2.0, 1.0, 0.0),
+            0.8,
+            Box::new(material::Metal::new(Color::new(0.8, 0.6, 0.2), 0.3)),
+        ));
+        let world = HittableVec::new(world);
+        let camera = studio_camera(1.5);
+        let mut settings = RenderSettings::default();
+        settings.glass_bounces(8).diffuse_bounces(8);
+        for i in 0..50 {
+            let (u, v) = (i as f64 / 50.0, 0.3 + 0.01 * i as f64);
+            let ray = camera.ray(u, v);
+            rng::reseed(i);
+            let iterative = ray_color(&ray, &world, settings.bounces, &settings);
+            rng::reseed(i);
+            let recursive = ray_color_recursive(&ray, &world, settings.bounces, &settings);
+            let diff = iterative - recursive;
+            assert!(diff.red.abs().max(diff.green.abs()).max(diff.blue.abs()) < 1e-12);
+        }
+    }
+    #[test]
+    fn russian_roulette_keeps_brightness() {
+        let world = HittableVec::new(studio_scene(Box::new(material::Lambertian::new(
+            Color::new(0.8, 0.8, 0.8),
+        ))));
+        let camera = studio_camera(1.0);
+        let brightness = |roulette: bool| {
+            let mut settings = RenderSettings::default();
+            settings
+                .aa_samples(256)
+                .diffuse_bounces(16)
+                .seed(Some(11))
+                .russian_roulette(roulette)
+                .progress(progress::ProgressStyle::Quiet);
+            let mut img = image::Image::new(6, 6);
+            fill_image(&mut img, &settings, &camera, &world, |_| true);
+            img.data
+                .iter()
+                .map(|c| c.red + c.green + c.blue)
+                .sum::<f64>()
+        };
+        let (fixed, roulette) = (brightness(false), brightness(true));
+        assert!((roulette - fixed).abs() < 0.02 * fixed);
+    }
 }
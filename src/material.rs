@@ -1,11 +1,16 @@
-use crate::image::Color;
+use crate::image::{colors, Color};
 use crate::ray::{HitRecord, Ray};
+use crate::rng;
+use crate::texture::{SolidColor, Texture};
 use crate::vec::{self, Vector};
-use rand::{self, Rng};
+use rand::Rng;
 
 pub struct MaterialEffect {
     pub attenuation: Color,
     pub scattered: Option<Ray>,
+    /// The scattered direction is the only possible one, as for perfect
+    /// mirrors and clear glass
+    pub is_specular: bool,
 }
 
 impl std::default::Default for MaterialEffect {
@@ -13,6 +18,7 @@ impl std::default::Default for MaterialEffect {
         Self {
             attenuation: Color::new(0.0, 0.0, 0.0),
             scattered: None,
+            is_specular: false,
         }
     }
 }
@@ -22,36 +28,146 @@ impl MaterialEffect {
         Self {
             attenuation,
             scattered: Some(scatter),
+            is_specular: false,
+        }
+    }
+    pub fn specular(attenuation: Color, scatter: Ray) -> Self {
+        Self {
+            attenuation,
+            scattered: Some(scatter),
+            is_specular: true,
         }
     }
     pub fn with_attenuation(attenuation: Color) -> Self {
         Self {
             attenuation,
             scattered: None,
+            is_specular: false,
         }
     }
 }
 
-pub trait Material: std::fmt::Debug {
+/// Kind of material a bounce is counted against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BounceCategory {
+    Diffuse,
+    Glass,
+}
+
+pub trait Material: std::fmt::Debug + Send + Sync {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> MaterialEffect;
+    /// Surface color at `hit`, used for previews
+    fn albedo(&self, hit: &HitRecord) -> Color;
+    /// BRDF value at `hit` for light coming from `wi` and leaving toward
+    /// `wo`, both pointing away from the surface. Specular materials scatter
+    /// along a single direction, which any other pair of directions doesn't
+    /// match.
+    fn eval_brdf(&self, _hit: &HitRecord, _wi: &Vector, _wo: &Vector) -> Color {
+        colors::BLACK
+    }
+    fn bounce_category(&self) -> BounceCategory {
+        BounceCategory::Diffuse
+    }
+    /// Light given off by the surface
+    fn emitted(&self) -> Color {
+        colors::BLACK
+    }
 }
 
+/// Light source emitting evenly from its surface, it doesn't reflect light
 #[derive(Debug, Clone, Copy)]
-pub struct Lambertian {
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit: &HitRecord) -> MaterialEffect {
+        MaterialEffect::default()
+    }
+
+    fn albedo(&self, _hit: &HitRecord) -> Color {
+        self.emit
+    }
+
+    fn emitted(&self) -> Color {
+        self.emit
+    }
+}
+
+/// Scatters evenly in every direction, the phase function of fog and smoke
+#[derive(Debug, Clone, Copy)]
+pub struct Isotropic {
     albedo: Color,
 }
 
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> MaterialEffect {
+        let scattered = Ray::timed(hit.point, vec::random_unit_vector(), ray.time);
+        MaterialEffect::new(self.albedo, scattered)
+    }
+
+    fn albedo(&self, _hit: &HitRecord) -> Color {
+        self.albedo
+    }
+}
+
+// cosine weighted direction around `normal`, falls back to the normal when
+// `random` cancels it out
+fn diffuse_direction(normal: &Vector, random: &Vector) -> Vector {
+    let dir = normal + random;
+    if dir.near_zero() {
+        *normal
+    } else {
+        dir
+    }
+}
+
+#[derive(Debug)]
+pub struct Lambertian {
+    albedo: Box<dyn Texture>,
+}
+
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
+        Self::textured(Box::new(SolidColor(albedo)))
+    }
+
+    pub fn textured(albedo: Box<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit: &HitRecord) -> MaterialEffect {
-        let scatter_dir = hit.normal + vec::random_unit_vector();
-        let scattered = Ray::new(hit.point, scatter_dir);
-        MaterialEffect::new(self.albedo, scattered)
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> MaterialEffect {
+        let scatter_dir = diffuse_direction(&hit.normal, &vec::random_unit_vector());
+        let scattered = Ray::timed(hit.point, scatter_dir, ray.time);
+        let albedo = self.albedo.value(hit.u, hit.v, &hit.point);
+        MaterialEffect::new(albedo, scattered)
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Color {
+        self.albedo.value(hit.u, hit.v, &hit.point)
+    }
+
+    fn eval_brdf(&self, hit: &HitRecord, wi: &Vector, wo: &Vector) -> Color {
+        // light is reflected evenly over the hemisphere
+        if vec::dot(wi, &hit.normal) > 0.0 && vec::dot(wo, &hit.normal) > 0.0 {
+            &self.albedo(hit) / std::f64::consts::PI
+        } else {
+            colors::BLACK
+        }
     }
 }
 
@@ -74,15 +190,24 @@ impl Material for Metal {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> MaterialEffect {
         let reflected = vec::reflect(&ray.direction, &hit.normal);
         if vec::dot(&reflected, &hit.normal) > 0.0 {
-            let scattered = Ray::new(
+            let scattered = Ray::timed(
                 hit.point,
                 reflected + self.fuzz * &vec::random_unit_vector(),
+                ray.time,
             );
-            MaterialEffect::new(self.albedo, scattered)
+            if self.fuzz == 0.0 {
+                MaterialEffect::specular(self.albedo, scattered)
+            } else {
+                MaterialEffect::new(self.albedo, scattered)
+            }
         } else {
             MaterialEffect::with_attenuation(self.albedo)
         }
     }
+
+    fn albedo(&self, _hit: &HitRecord) -> Color {
+        self.albedo
+    }
 }
 
 fn refract(incoming: &Vector, normal: &Vector, etai_over_etat: f64) -> Vector {
@@ -95,11 +220,35 @@ fn refract(incoming: &Vector, normal: &Vector, etai_over_etat: f64) -> Vector {
 #[derive(Debug, Clone, Copy)]
 pub struct Dielectric {
     refraction_index: f64,
+    roughness: f64,
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f64) -> Self {
-        Self { refraction_index }
+        Self::rough(refraction_index, 0.0)
+    }
+
+    /// Frosted glass, reflected and refracted rays are perturbed by `roughness`
+    pub fn rough(refraction_index: f64, roughness: f64) -> Self {
+        Self {
+            refraction_index,
+            roughness: if roughness < 1.0 { roughness } else { 1.0 },
+        }
+    }
+
+    // perturb `dir` while keeping it on the same side of the surface
+    fn roughen(&self, dir: Vector, normal: &Vector) -> Vector {
+        if self.roughness == 0.0 {
+            return dir;
+        }
+        let side = vec::dot(&dir, normal);
+        for _ in 0..8 {
+            let rough_dir = dir + self.roughness * vec::random_unit_vector();
+            if vec::dot(&rough_dir, normal) * side > 0.0 {
+                return rough_dir;
+            }
+        }
+        dir
     }
 }
 
@@ -119,13 +268,27 @@ impl Material for Dielectric {
         // sin(theta') <= 1 to refract so n/n' sin(theta) < 1
         // otherwise it reflects
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let rand_f64 = rand::thread_rng().gen_range(0.0, 1.0);
+        let rand_f64 = rng::local().gen_range(0.0, 1.0);
         let new_ray_dir = if cannot_refract || reflectance(cos_theta, refraction_ratio) > rand_f64 {
             vec::reflect(&unit_dir, &hit.normal)
         } else {
             refract(&unit_dir, &hit.normal, refraction_ratio)
         };
-        MaterialEffect::new(no_attenuation, Ray::new(hit.point, new_ray_dir))
+        let new_ray_dir = self.roughen(new_ray_dir, &hit.normal);
+        let scattered = Ray::timed(hit.point, new_ray_dir, ray.time);
+        if self.roughness == 0.0 {
+            MaterialEffect::specular(no_attenuation, scattered)
+        } else {
+            MaterialEffect::new(no_attenuation, scattered)
+        }
+    }
+
+    fn albedo(&self, _hit: &HitRecord) -> Color {
+        colors::WHITE
+    }
+
+    fn bounce_category(&self) -> BounceCategory {
+        BounceCategory::Glass
     }
 }
 
@@ -134,3 +297,146 @@ fn reflectance(cos: f64, refr_ratio: f64) -> f64 {
     r0 = r0 * r0;
     r0 + (1.0 - r0) * (1.0 - cos).powi(5)
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::vec::Point;
+
+    /// Mid gray diffuse material for objects whose look does not matter
+    pub fn gray() -> Box<dyn Material> {
+        Box::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    // directions of the rays transmitted through a surface facing +z
+    fn transmitted(glass: Dielectric) -> Vec<Vector> {
+        let material: Box<dyn Material> = Box::new(glass);
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let hit = HitRecord::new(Point::new(0.0, 0.0, 0.0), normal, 1.0, true, &material);
+        let ray = Ray::new(Point::new(0.3, 0.0, 1.0), Vector::new(-0.3, 0.0, -1.0));
+        (0..500)
+            .filter_map(|_| material.scatter(&ray, &hit).scattered)
+            .map(|r| vec::unit(&r.direction))
+            .filter(|d| vec::dot(d, &normal) < 0.0)
+            .collect()
+    }
+
+    fn spread(dirs: &[Vector]) -> f64 {
+        dirs.iter()
+            .map(|d| (d - dirs[0]).length())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn clear_glass_is_deterministic() {
+        let dirs = transmitted(Dielectric::new(1.5));
+        assert!(!dirs.is_empty());
+        assert_eq!(0.0, spread(&dirs));
+    }
+
+    #[test]
+    fn rough_glass_spreads_transmission() {
+        let dirs = transmitted(Dielectric::rough(1.5, 0.5));
+        assert!(!dirs.is_empty());
+        assert!(spread(&dirs) > 0.1);
+    }
+
+    #[test]
+    fn lambertian_brdf_is_constant_over_hemisphere() {
+        let lambertian: Box<dyn Material> = Box::new(Lambertian::new(Color::new(0.6, 0.3, 0.9)));
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        let hit = HitRecord::new(Point::new(0.0, 0.0, 0.0), normal, 1.0, true, &lambertian);
+        let expected = 0.6 / std::f64::consts::PI;
+        for _ in 0..100 {
+            let wi = vec::random_unit_vector();
+            let wo = vec::random_unit_vector();
+            let brdf = lambertian.eval_brdf(&hit, &wi, &wo);
+            if wi.y > 0.0 && wo.y > 0.0 {
+                assert!((brdf.red - expected).abs() < 1e-12);
+                assert!((brdf.green - expected / 2.0).abs() < 1e-12);
+            } else {
+                assert_eq!((0.0, 0.0, 0.0), (brdf.red, brdf.green, brdf.blue));
+            }
+        }
+    }
+
+    #[test]
+    fn mirror_and_glass_bounces_are_specular() {
+        let surface = |material: Box<dyn Material>| {
+            let normal = Vector::new(0.0, 0.0, 1.0);
+            let hit = HitRecord::new(Point::new(0.0, 0.0, 0.0), normal, 1.0, true, &material);
+            let ray = Ray::new(Point::new(0.3, 0.0, 1.0), Vector::new(-0.3, 0.0, -1.0));
+            material.scatter(&ray, &hit).is_specular
+        };
+        let white = Color::new(0.8, 0.8, 0.8);
+        assert!(surface(Box::new(Metal::new(white, 0.0))));
+        assert!(surface(Box::new(Dielectric::new(1.5))));
+        assert!(!surface(Box::new(Metal::new(white, 0.3))));
+        assert!(!surface(Box::new(Dielectric::rough(1.5, 0.5))));
+        assert!(!surface(Box::new(Lambertian::new(white))));
+    }
+
+    #[test]
+    fn degenerate_diffuse_direction_falls_back_to_normal() {
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        let opposite = Vector::new(1e-9, -1.0, 0.0);
+        assert!((normal + opposite).near_zero());
+        assert_eq!(normal, diffuse_direction(&normal, &opposite));
+        let random = vec::unit(&Vector::new(1.0, 1.0, 0.0));
+        assert_eq!(normal + random, diffuse_direction(&normal, &random));
+    }
+
+    #[test]
+    fn diffuse_light_emits_without_scattering() {
+        let material: Box<dyn Material> = Box::new(DiffuseLight::new(Color::new(4.0, 2.0, 1.0)));
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let hit = HitRecord::new(Point::new(0.0, 0.0, 0.0), normal, 1.0, true, &material);
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(material.scatter(&ray, &hit).scattered.is_none());
+        let emitted = material.emitted();
+        assert_eq!((4.0, 2.0, 1.0), (emitted.red, emitted.green, emitted.blue));
+        let emitted = Lambertian::new(Color::new(0.5, 0.5, 0.5)).emitted();
+        assert_eq!((0.0, 0.0, 0.0), (emitted.red, emitted.green, emitted.blue));
+    }
+
+    #[test]
+    fn specular_brdf_is_zero() {
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        let wi = vec::unit(&Vector::new(1.0, 1.0, 0.0));
+        let wo = vec::unit(&Vector::new(-1.0, 1.0, 0.0));
+        let brdf = |material: Box<dyn Material>| {
+            let hit = HitRecord::new(Point::new(0.0, 0.0, 0.0), normal, 1.0, true, &material);
+            material.eval_brdf(&hit, &wi, &wo).red
+        };
+        assert_eq!(
+            0.0,
+            brdf(Box::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.0)))
+        );
+        assert_eq!(0.0, brdf(Box::new(Dielectric::new(1.5))));
+    }
+
+    #[test]
+    fn textured_albedo_follows_the_hit() {
+        use crate::texture::Checker;
+        let (black, white) = (Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+        let checker: Box<dyn Material> = Box::new(Lambertian::textured(Box::new(Checker::colors(
+            black, white, 1.0,
+        ))));
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        let at = |p: Point| {
+            let hit = HitRecord::new(p, normal, 1.0, true, &checker);
+            let wi = Vector::new(0.0, 1.0, 0.0);
+            (
+                checker.albedo(&hit).red,
+                checker.eval_brdf(&hit, &wi, &wi).red,
+            )
+        };
+        let (odd, even) = (
+            at(Point::new(-1.0, 1.0, 1.0)),
+            at(Point::new(1.0, 1.0, 1.0)),
+        );
+        assert_ne!(odd.0, even.0);
+        assert_eq!(odd.0 / std::f64::consts::PI, odd.1);
+        assert_eq!(even.0 / std::f64::consts::PI, even.1);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::{HitRecord, Hittable, Ray};
+use crate::rng;
+use crate::vec::Vector;
+use rand::Rng;
+
+/// Volume of even density, like fog or smoke, filling a convex `boundary`.
+/// Rays go through it for a random distance before scattering with `phase`.
+pub struct ConstantMedium {
+    pub boundary: Box<dyn Hittable>,
+    pub density: f64,
+    pub phase: Box<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Box<dyn Hittable>, density: f64, phase: Box<dyn Material>) -> Self {
+        ConstantMedium {
+            boundary,
+            density,
+            phase,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        // where the whole line enters and leaves the boundary, the ray may
+        // start inside
+        let enter = self.boundary.hit(ray, Interval::UNIVERSE)?.t;
+        let leave = self
+            .boundary
+            .hit(ray, Interval::new(enter + 0.0001, f64::INFINITY))?
+            .t;
+        let enter = enter.max(t_range.min).max(0.0);
+        let leave = leave.min(t_range.max);
+        if enter >= leave {
+            return None;
+        }
+        // distances to a scattering follow an exponential distribution
+        let ray_length = ray.direction.length();
+        let inside = (leave - enter) * ray_length;
+        let random: f64 = rng::local().gen_range(0.0, 1.0);
+        let distance = -random.ln() / self.density;
+        if distance > inside {
+            return None;
+        }
+        let t = enter + distance / ray_length;
+        // volumes have no surface, any normal will do
+        let normal = Vector::new(1.0, 0.0, 0.0);
+        Some(HitRecord::new(ray.at(t), normal, t, true, &self.phase))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+    use crate::material::Isotropic;
+    use crate::sphere::Sphere;
+    use crate::vec::Point;
+
+    fn fog(density: f64) -> ConstantMedium {
+        let gray = Color::new(0.5, 0.5, 0.5);
+        let boundary = Sphere::new(
+            Point::new(0.0, 0.0, -3.0),
+            1.0,
+            Box::new(Isotropic::new(gray)),
+        );
+        ConstantMedium::new(Box::new(boundary), density, Box::new(Isotropic::new(gray)))
+    }
+
+    #[test]
+    fn denser_fog_scatters_more() {
+        rng::reseed(5);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let ahead = Interval::new(0.001, f64::MAX);
+        let scatters = |density: f64| {
+            let medium = fog(density);
+            (0..1000)
+                .filter_map(|_| medium.hit(&ray, ahead))
+                .inspect(|hit| assert!(hit.t > 2.0 && hit.t < 4.0))
+                .count()
+        };
+        // 1 - exp(-2 density) of the rays scatter
+        let (thin, thick, opaque) = (scatters(0.1), scatters(1.0), scatters(20.0));
+        assert!(thin < thick && thick < opaque);
+        assert!(thin > 100 && thin < 260);
+        assert_eq!(1000, opaque);
+        // beyond the range or outside of the boundary
+        assert!(fog(20.0).hit(&ray, Interval::new(0.001, 2.0)).is_none());
+        let beside = Ray::new(Point::new(2.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(fog(20.0).hit(&beside, ahead).is_none());
+    }
+}
@@ -0,0 +1,210 @@
+use crate::error::RenderError;
+use crate::material::Material;
+use crate::triangle::Triangle;
+use crate::vec::{Point, Vector};
+use std::sync::Arc;
+
+/// Triangle of an OBJ file, as indices into the vertex and normal lists
+#[derive(Debug, PartialEq)]
+struct Face {
+    vertices: [usize; 3],
+    /// only when all three corners have a normal
+    normals: Option<[usize; 3]>,
+}
+
+/// Vertices, normals and triangulated faces of a Wavefront OBJ file
+struct ObjMesh {
+    vertices: Vec<Point>,
+    normals: Vec<Vector>,
+    faces: Vec<Face>,
+}
+
+// position in a list of `count` items of the one referred to by `token`,
+// OBJ indices start at 1 and negative ones count back from the last item read
+fn index(token: &str, count: usize) -> Option<usize> {
+    let index: i64 = token.parse().ok()?;
+    let position = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if position >= 0 && (position as usize) < count {
+        Some(position as usize)
+    } else {
+        None
+    }
+}
+
+// vertex and normal indices of a face corner, written `v`, `v/vt`, `v//vn`
+// or `v/vt/vn`, texture coordinates are ignored
+fn corner(token: &str, mesh: &ObjMesh) -> Result<(usize, Option<usize>), String> {
+    let mut parts = token.split('/');
+    let vertex = parts
+        .next()
+        .and_then(|v| index(v, mesh.vertices.len()))
+        .ok_or_else(|| format!("invalid vertex index {}", token))?;
+    let normal = match parts.nth(1) {
+        Some(n) => Some(
+            index(n, mesh.normals.len())
+                .ok_or_else(|| format!("invalid normal index {}", token))?,
+        ),
+        None => None,
+    };
+    Ok((vertex, normal))
+}
+
+// three coordinates following a `v` or `vn` directive
+fn coordinates<'a>(fields: impl Iterator<Item = &'a str>) -> Result<[f64; 3], String> {
+    let coords: Vec<f64> = fields
+        .take(3)
+        .map(|f| f.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("{}", e))?;
+    if coords.len() < 3 {
+        return Err("3 coordinates are needed".to_string());
+    }
+    Ok([coords[0], coords[1], coords[2]])
+}
+
+fn parse_obj(text: &str) -> Result<ObjMesh, String> {
+    let mut mesh = ObjMesh {
+        vertices: Vec::new(),
+        normals: Vec::new(),
+        faces: Vec::new(),
+    };
+    for (number, line) in text.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("v") => {
+                let coords = coordinates(fields)
+                    .map_err(|e| format!("line {}: invalid vertex: {}", number + 1, e))?;
+                mesh.vertices.push(Point::from(coords));
+            }
+            Some("vn") => {
+                let coords = coordinates(fields)
+                    .map_err(|e| format!("line {}: invalid normal: {}", number + 1, e))?;
+                mesh.normals.push(Vector::from(coords));
+            }
+            Some("f") => {
+                let corners: Vec<(usize, Option<usize>)> = fields
+                    .map(|f| corner(f, &mesh).map_err(|e| format!("line {}: {}", number + 1, e)))
+                    .collect::<Result<_, _>>()?;
+                if corners.len() < 3 {
+                    return Err(format!("line {}: face needs 3 vertices", number + 1));
+                }
+                // fan around the first vertex
+                for i in 1..corners.len() - 1 {
+                    let (a, b, c) = (corners[0], corners[i], corners[i + 1]);
+                    let normals = match (a.1, b.1, c.1) {
+                        (Some(na), Some(nb), Some(nc)) => Some([na, nb, nc]),
+                        _ => None,
+                    };
+                    mesh.faces.push(Face {
+                        vertices: [a.0, b.0, c.0],
+                        normals,
+                    });
+                }
+            }
+            // comments, blank lines and other directives
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
+
+/// Triangles of the OBJ file at `path`, all made of `material`, smoothly
+/// shaded where the file gives vertex normals
+pub fn load_obj(path: &str, material: Box<dyn Material>) -> Result<Vec<Triangle>, RenderError> {
+    let text = std::fs::read_to_string(path)?;
+    let mesh =
+        parse_obj(&text).map_err(|e| RenderError::InvalidMesh(format!("{}: {}", path, e)))?;
+    let material = Arc::new(material);
+    Ok(mesh
+        .faces
+        .iter()
+        .map(|face| {
+            let [a, b, c] = face.vertices;
+            let triangle = Triangle::new(
+                mesh.vertices[a],
+                mesh.vertices[b],
+                mesh.vertices[c],
+                Arc::clone(&material),
+            );
+            match face.normals {
+                Some([na, nb, nc]) => {
+                    triangle.with_normals(mesh.normals[na], mesh.normals[nb], mesh.normals[nc])
+                }
+                None => triangle,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::test::gray;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[test]
+    fn quad_is_split_in_two() {
+        let text = std::fs::read_to_string(fixture("quad.obj")).unwrap();
+        let mesh = parse_obj(&text).unwrap();
+        assert_eq!(4, mesh.vertices.len());
+        assert_eq!(2, mesh.faces.len());
+        let triangles = load_obj(&fixture("quad.obj"), gray()).unwrap();
+        assert_eq!(2, triangles.len());
+        let centroid = |t: &Triangle| (t.a + t.b + t.c) / 3.0;
+        assert!(centroid(&triangles[0]).approx_eq(&Point::new(1.0 / 3.0, -1.0 / 3.0, 0.0), 1e-12));
+        assert!(centroid(&triangles[1]).approx_eq(&Point::new(-1.0 / 3.0, 1.0 / 3.0, 0.0), 1e-12));
+    }
+
+    #[test]
+    fn relative_indices_and_polygons() {
+        let mesh = parse_obj(
+            "# pentagon\no shape\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0.5 2 0\nv 0 1 0\n\
+             vn 0 0 1\nf -5/1/1 -4/2/1 -3 -2 -1\n",
+        )
+        .unwrap();
+        let vertices: Vec<[usize; 3]> = mesh.faces.iter().map(|f| f.vertices).collect();
+        assert_eq!(vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]], vertices);
+        // the third corner has no normal
+        assert!(mesh.faces.iter().all(|f| f.normals.is_none()));
+    }
+
+    #[test]
+    fn vertex_normals_are_read() {
+        let mesh = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nvn 0 1 1\nf 1//1 2//2 3/7/-1\n")
+            .unwrap();
+        assert_eq!(2, mesh.normals.len());
+        assert_eq!(
+            vec![Face {
+                vertices: [0, 1, 2],
+                normals: Some([0, 1, 1]),
+            }],
+            mesh.faces
+        );
+    }
+
+    #[test]
+    fn malformed_faces_are_reported() {
+        let err = |text: &str| parse_obj(text).err().unwrap();
+        assert_eq!("line 2: face needs 3 vertices", err("v 0 0 0\nf 1 1\n"));
+        assert_eq!("line 2: invalid vertex index 2", err("v 0 0 0\nf 1 2 1\n"));
+        assert_eq!("line 1: invalid vertex index 0", err("f 0 1 2\n"));
+        assert!(err("v 0 zero 0\n").starts_with("line 1: invalid vertex"));
+        assert_eq!(
+            "line 3: invalid normal index 1//2",
+            err("v 0 0 0\nvn 0 0 1\nf 1//2 1//1 1//1\n")
+        );
+        assert_eq!(
+            "line 3: invalid vertex index 2//1",
+            err("v 0 0 0\nvn 0 0 1\nf 2//1 1//1 1//1\n")
+        );
+        let e = load_obj("missing.obj", gray()).err().unwrap();
+        assert!(matches!(e, RenderError::Io(_)));
+    }
+}
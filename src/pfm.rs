@@ -0,0 +1,64 @@
+use crate::image::Image;
+use std::io;
+
+/// Write `img` as a little-endian Portable Float Map, colors are written
+/// as they are, without any clamping or gamma correction
+pub fn write_pfm<W: io::Write>(mut writer: W, img: &Image) -> io::Result<()> {
+    // a negative scale means little-endian
+    writer.write_all(format!("PF\n{} {}\n-1.0\n", img.width, img.height).as_bytes())?;
+    // scanlines are stored from bottom to top
+    for l in (0..img.height).rev() {
+        for px in &img.data[l * img.width..(l + 1) * img.width] {
+            for val in &[px.red, px.green, px.blue] {
+                writer.write_all(&(*val as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+
+    // decode (width, height, pixels top to bottom) from a little-endian PFM
+    fn read_pfm(data: &[u8]) -> (usize, usize, Vec<f32>) {
+        let mut header = data.splitn(4, |&b| b == b'\n');
+        assert_eq!(b"PF", header.next().unwrap());
+        let size = String::from_utf8(header.next().unwrap().to_vec()).unwrap();
+        let mut size = size.split(' ').map(|v| v.parse::<usize>().unwrap());
+        let (width, height) = (size.next().unwrap(), size.next().unwrap());
+        assert_eq!(b"-1.0", header.next().unwrap());
+        let floats: Vec<f32> = header
+            .next()
+            .unwrap()
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(width * height * 3, floats.len());
+        let mut pixels = Vec::with_capacity(floats.len());
+        for l in (0..height).rev() {
+            pixels.extend_from_slice(&floats[l * width * 3..(l + 1) * width * 3]);
+        }
+        (width, height, pixels)
+    }
+
+    #[test]
+    fn round_trip_keeps_hdr_values() {
+        let mut img = Image::new(3, 2);
+        for (i, px) in img.data.iter_mut().enumerate() {
+            let i = i as f64;
+            *px = Color::new(i * 1.5, 0.25 + i, 10.0 - i);
+        }
+        let mut out = Vec::new();
+        write_pfm(&mut out, &img).unwrap();
+        let (width, height, pixels) = read_pfm(&out);
+        assert_eq!((3, 2), (width, height));
+        for (px, rgb) in img.data.iter().zip(pixels.chunks(3)) {
+            assert_eq!(px.red as f32, rgb[0]);
+            assert_eq!(px.green as f32, rgb[1]);
+            assert_eq!(px.blue as f32, rgb[2]);
+        }
+    }
+}
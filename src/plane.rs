@@ -0,0 +1,83 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::{HitRecord, Hittable, Ray};
+use crate::vec::{self, Point, Vector};
+
+/// Infinite plane going through `point`, facing `normal`
+#[derive(Debug)]
+pub struct Plane {
+    pub point: Point,
+    pub normal: Vector,
+    pub material: Box<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vector, material: Box<dyn Material>) -> Self {
+        Plane {
+            point,
+            normal: vec::unit(&normal),
+            material,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        // P is on the plane if (P - Q).N = 0, so (O + tD - Q).N = 0
+        // and t = (Q - O).N / D.N, no hit when the ray is parallel
+        let denom = vec::dot(&ray.direction, &self.normal);
+        if denom == 0.0 {
+            return None;
+        }
+        let t = vec::dot(&(self.point - ray.origin), &self.normal) / denom;
+        if !t_range.surrounds(t) {
+            return None;
+        }
+        let front = denom < 0.0;
+        Some(HitRecord::new(
+            ray.at(t),
+            self.normal,
+            t,
+            front,
+            &self.material,
+        ))
+    }
+
+    /// Unbounded, planes can't be framed
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(Interval::UNIVERSE, Interval::UNIVERSE, Interval::UNIVERSE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::test::gray;
+    #[test]
+    fn plane_is_hit_from_both_sides() {
+        let floor = Plane::new(
+            Point::new(0.0, -1.0, 0.0),
+            Vector::new(0.0, 2.0, 0.0),
+            gray(),
+        );
+        let ahead = Interval::new(0.001, f64::MAX);
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let hit = floor
+            .hit(&Ray::new(origin, Vector::new(1.0, -1.0, 0.0)), ahead)
+            .unwrap();
+        assert_eq!(1.0, hit.t);
+        assert!(hit.front_face);
+        assert_eq!(Vector::new(0.0, 1.0, 0.0), hit.normal);
+        let below = Point::new(0.0, -3.0, 0.0);
+        let hit = floor
+            .hit(&Ray::new(below, Vector::new(0.0, 1.0, 0.0)), ahead)
+            .unwrap();
+        assert!(!hit.front_face);
+        assert_eq!(Vector::new(0.0, -1.0, 0.0), hit.normal);
+        let parallel = Ray::new(origin, Vector::new(1.0, 0.0, 0.0));
+        assert!(floor.hit(&parallel, ahead).is_none());
+        let away = Ray::new(origin, Vector::new(0.0, 1.0, 0.0));
+        assert!(floor.hit(&away, ahead).is_none());
+    }
+}
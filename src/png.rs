@@ -0,0 +1,54 @@
+use crate::error::RenderError;
+use crate::image::Image;
+use ::image::ColorType;
+
+/// Write `img` to `path` as an 8 bits RGB PNG, colors are expected in [0, 1)
+pub fn write_png(path: &str, img: &Image) -> Result<(), RenderError> {
+    let mut rgb8 = Vec::with_capacity(3 * img.data.len());
+    for px in &img.data {
+        rgb8.extend_from_slice(&px.to_rgb8());
+    }
+    ::image::save_buffer(
+        path,
+        &rgb8,
+        img.width as u32,
+        img.height as u32,
+        ColorType::Rgb8,
+    )
+    .map_err(|e| RenderError::Io(format!("{}: {}", path, e)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+
+    #[test]
+    fn gradient_decodes_with_image_crate() {
+        let mut img = Image::new(4, 3);
+        for (index, px) in img.data.iter_mut().enumerate() {
+            let (col, line) = ((index % 4) as f64, (index / 4) as f64);
+            *px = Color::new(col / 4.0, line / 3.0, 0.5);
+        }
+        let path =
+            std::env::temp_dir().join(format!("raytracer-{}-gradient.png", std::process::id()));
+        let path = path.to_str().unwrap();
+        write_png(path, &img).unwrap();
+        let decoded = ::image::open(path).map(|img| img.to_rgb8());
+        std::fs::remove_file(path).unwrap();
+        let decoded = decoded.unwrap();
+        assert_eq!((4, 3), decoded.dimensions());
+        let raw = decoded.into_raw();
+        let px = |col: usize, line: usize| &raw[3 * (line * 4 + col)..3 * (line * 4 + col + 1)];
+        assert_eq!([0, 0, 128], px(0, 0));
+        assert_eq!([191, 0, 128], px(3, 0));
+        assert_eq!([0, 170, 128], px(0, 2));
+        assert_eq!([191, 170, 128], px(3, 2));
+    }
+
+    #[test]
+    fn unwritable_paths_are_reported() {
+        let e = write_png("/nonexistent/dir/out.png", &Image::new(1, 1));
+        assert!(matches!(e, Err(RenderError::Io(_))));
+    }
+}
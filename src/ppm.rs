@@ -3,37 +3,113 @@ use std::io;
 
 pub struct PPMWriter<W: io::Write> {
     writer: W,
-}
-
-fn numerize(f: f64) -> u8 {
-    (f * 256.0) as u8
+    binary: bool,
 }
 
 impl<W: io::Write> PPMWriter<W> {
     pub fn new(writer: W) -> Self {
-        PPMWriter { writer }
+        PPMWriter {
+            writer,
+            binary: false,
+        }
+    }
+
+    /// Writer of raw bytes (P6) instead of decimal values (P3), several
+    /// times smaller
+    pub fn binary(writer: W) -> Self {
+        PPMWriter {
+            writer,
+            binary: true,
+        }
     }
 
     pub fn write(&mut self, img: &Image) -> io::Result<()> {
-        self.writer.write_all(b"P3\n")?;
+        assert_eq!(
+            img.width * img.height,
+            img.data.len(),
+            "Image data doesn't match its {}x{} size",
+            img.width,
+            img.height
+        );
+        let magic: &[u8] = if self.binary { b"P6\n" } else { b"P3\n" };
+        self.writer.write_all(magic)?;
         self.writer
             .write_all(format!("{} {}\n", img.width, img.height).as_bytes())?;
         self.writer.write_all(b"255\n")?;
         for l in 0..img.height {
             for c in 0..img.width {
-                let px = &img.data[l * img.width + c];
-                self.writer.write_all(
-                    format!(
-                        "{} {} {} ",
-                        numerize(px.red),
-                        numerize(px.green),
-                        numerize(px.blue)
-                    )
-                    .as_bytes(),
-                )?;
+                let [r, g, b] = img.data[l * img.width + c].to_rgb8();
+                if self.binary {
+                    self.writer.write_all(&[r, g, b])?;
+                } else {
+                    self.writer
+                        .write_all(format!("{} {} {} ", r, g, b).as_bytes())?;
+                }
+            }
+            if !self.binary {
+                self.writer.write_all(b"\n")?;
             }
-            self.writer.write_all(b"\n")?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+    #[test]
+    fn pixels_are_written_row_by_row() {
+        let mut img = Image::new(3, 2);
+        for (i, px) in img.data.iter_mut().enumerate() {
+            let i = i as f64;
+            *px = Color::new(i / 8.0, 0.5, 0.999 - i / 8.0);
+        }
+        let mut out = Vec::new();
+        PPMWriter::new(&mut out).write(&img).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let mut lines = out.lines();
+        assert_eq!(Some("P3"), lines.next());
+        assert_eq!(Some("3 2"), lines.next());
+        assert_eq!(Some("255"), lines.next());
+        for line in 0..2 {
+            let values: Vec<u8> = lines
+                .next()
+                .unwrap()
+                .split_whitespace()
+                .map(|v| v.parse().unwrap())
+                .collect();
+            assert_eq!(9, values.len());
+            for col in 0..3 {
+                let expected = img.data[line * 3 + col].to_rgb8();
+                assert_eq!(expected, values[3 * col..3 * col + 3]);
+            }
+        }
+        assert_eq!(None, lines.next());
+    }
+    #[test]
+    fn binary_matches_ascii() {
+        let mut img = Image::new(3, 2);
+        img.data[0] = Color::new(0.999, 0.0, 0.5);
+        img.data[4] = Color::new(1.2, -0.1, 0.25);
+        let mut ascii = Vec::new();
+        PPMWriter::new(&mut ascii).write(&img).unwrap();
+        let mut binary = Vec::new();
+        PPMWriter::binary(&mut binary).write(&img).unwrap();
+        let ascii = String::from_utf8(ascii).unwrap();
+        let mut ascii = ascii.split_whitespace();
+        assert_eq!(Some("P3"), ascii.next());
+        let header = "P6\n3 2\n255\n".as_bytes();
+        assert_eq!(header, &binary[..header.len()]);
+        let ascii: Vec<u8> = ascii.skip(3).map(|v| v.parse().unwrap()).collect();
+        assert_eq!(ascii, &binary[header.len()..]);
+        assert_eq!([255, 0, 64], ascii[12..15]);
+    }
+    #[test]
+    #[should_panic]
+    fn mismatched_data_panics() {
+        let mut img = Image::new(3, 2);
+        img.data.pop();
+        PPMWriter::new(Vec::new()).write(&img).unwrap();
+    }
+}
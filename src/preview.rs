@@ -0,0 +1,74 @@
+#[cfg(any(feature = "gui", test))]
+use crate::image::Image;
+#[cfg(feature = "gui")]
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+/// Pack the colors of `img` as 0xAARRGGBB pixels, fully opaque, top to bottom
+#[cfg(any(feature = "gui", test))]
+pub fn to_argb(img: &Image) -> Vec<u32> {
+    img.data
+        .iter()
+        .map(|px| {
+            let [r, g, b] = px.to_rgb8();
+            0xff00_0000 | (r as u32) << 16 | (g as u32) << 8 | b as u32
+        })
+        .collect()
+}
+
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreviewAction {
+    Continue,
+    Save,
+    Quit,
+}
+
+/// Window displaying the image as it converges
+#[cfg(feature = "gui")]
+pub struct Preview {
+    window: Window,
+}
+
+#[cfg(feature = "gui")]
+impl Preview {
+    pub fn new(width: usize, height: usize) -> Self {
+        let window = Window::new("ray", width, height, WindowOptions::default())
+            .expect("Failed to open preview window");
+        Preview { window }
+    }
+
+    /// Display `img` and report what the user asked for: S saves,
+    /// Escape, Q or closing the window quits
+    pub fn show(&mut self, img: &Image) -> PreviewAction {
+        self.window
+            .update_with_buffer(&to_argb(img), img.width, img.height)
+            .expect("Failed to update preview window");
+        if !self.window.is_open()
+            || self.window.is_key_down(Key::Escape)
+            || self.window.is_key_down(Key::Q)
+        {
+            PreviewAction::Quit
+        } else if self.window.is_key_pressed(Key::S, KeyRepeat::No) {
+            PreviewAction::Save
+        } else {
+            PreviewAction::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+    #[test]
+    fn argb_buffer_matches_image() {
+        let mut img = Image::new(3, 2);
+        img.data[0] = Color::new(0.999, 0.0, 0.5);
+        img.data[5] = Color::new(0.25, 0.75, 0.999);
+        let buffer = to_argb(&img);
+        assert_eq!(3 * 2, buffer.len());
+        assert_eq!(0xffff_0080, buffer[0]);
+        assert_eq!(0xff00_0000, buffer[1]);
+        assert_eq!(0xff40_bfff, buffer[5]);
+    }
+}
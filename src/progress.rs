@@ -0,0 +1,113 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+// least time between two reports, in milliseconds
+const REPORT_PERIOD: u64 = 200;
+
+/// What the render reports on stderr as it goes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressStyle {
+    Quiet,
+    Percent,
+    /// Percentage with elapsed time and estimated time left
+    Eta,
+}
+
+/// Count of rendered pixels, shared by the rendering threads
+pub struct Progress {
+    style: ProgressStyle,
+    total: usize,
+    done: AtomicUsize,
+    start: Instant,
+    // milliseconds since start of the last report
+    last_report: AtomicU64,
+}
+
+impl Progress {
+    pub fn new(style: ProgressStyle, total: usize) -> Self {
+        Self {
+            style,
+            total,
+            done: AtomicUsize::new(0),
+            start: Instant::now(),
+            last_report: AtomicU64::new(0),
+        }
+    }
+
+    /// Count `pixels` more rendered pixels, and report if it's been a while
+    pub fn advance(&self, pixels: usize) {
+        let done = self.done.fetch_add(pixels, Ordering::SeqCst) + pixels;
+        if self.style == ProgressStyle::Quiet {
+            return;
+        }
+        let elapsed = self.start.elapsed();
+        let now = elapsed.as_millis() as u64;
+        let last = self.last_report.load(Ordering::SeqCst);
+        let finished = done >= self.total;
+        // a single thread wins the report of a period
+        if !finished
+            && (now < last + REPORT_PERIOD
+                || self
+                    .last_report
+                    .compare_exchange(last, now, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_err())
+        {
+            return;
+        }
+        let elapsed = match self.style {
+            ProgressStyle::Eta => Some(elapsed),
+            _ => None,
+        };
+        let mut stderr = io::stderr();
+        let _ = write!(stderr, "\r{}", format_progress(done, self.total, elapsed));
+        if finished {
+            let _ = writeln!(stderr);
+        }
+        let _ = stderr.flush();
+    }
+}
+
+/// Percentage of `done` over `total`, followed by the time taken and left
+/// when `elapsed` is given
+pub fn format_progress(done: usize, total: usize, elapsed: Option<Duration>) -> String {
+    let ratio = if total == 0 {
+        1.0
+    } else {
+        done.min(total) as f64 / total as f64
+    };
+    let percent = format!("Rendered {:5.1}%", 100.0 * ratio);
+    match elapsed {
+        None => percent,
+        Some(elapsed) => {
+            let secs = elapsed.as_secs_f64();
+            let eta = if ratio > 0.0 {
+                format!("{:.0}s", secs * (1.0 - ratio) / ratio)
+            } else {
+                "?".to_string()
+            };
+            format!("{}, {:.0}s elapsed, ETA {}", percent, secs, eta)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn progress_formatting() {
+        assert_eq!("Rendered   0.0%", format_progress(0, 200, None));
+        assert_eq!("Rendered  42.5%", format_progress(85, 200, None));
+        assert_eq!("Rendered 100.0%", format_progress(200, 200, None));
+        assert_eq!("Rendered 100.0%", format_progress(0, 0, None));
+        let ten_secs = Some(Duration::from_secs(10));
+        assert_eq!(
+            "Rendered  25.0%, 10s elapsed, ETA 30s",
+            format_progress(50, 200, ten_secs)
+        );
+        assert_eq!(
+            "Rendered   0.0%, 10s elapsed, ETA ?",
+            format_progress(0, 200, ten_secs)
+        );
+    }
+}
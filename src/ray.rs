@@ -1,3 +1,5 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
 use crate::material::Material;
 use crate::vec::{Point, Vector};
 
@@ -5,11 +7,21 @@ use crate::vec::{Point, Vector};
 pub struct Ray {
     pub origin: Point,
     pub direction: Vector,
+    /// Instant the ray is cast at, for moving objects
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Vector) -> Ray {
-        Ray { origin, direction }
+        Ray::timed(origin, direction, 0.0)
+    }
+
+    pub fn timed(origin: Point, direction: Vector, time: f64) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn at(&self, t: f64) -> Point {
@@ -24,6 +36,9 @@ pub struct HitRecord<'a> {
     pub t: f64,
     pub material: &'a Box<dyn Material>,
     pub front_face: bool,
+    /// Surface coordinates of the point, in [0, 1]
+    pub u: f64,
+    pub v: f64,
 }
 
 impl<'a> HitRecord<'a> {
@@ -41,12 +56,44 @@ impl<'a> HitRecord<'a> {
             t,
             material,
             front_face,
+            u: 0.0,
+            v: 0.0,
         }
     }
+
+    pub fn with_uv(self, u: f64, v: f64) -> Self {
+        Self { u, v, ..self }
+    }
 }
 
-pub trait Hittable {
-    fn hit_by(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+pub trait Hittable: Sync {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord>;
+    /// Box enclosing the whole object
+    fn bounding_box(&self) -> Aabb;
+    /// Box enclosing the parts of the object with finite bounds, empty when
+    /// the object is unbounded like a plane
+    fn bounded_box(&self) -> Aabb {
+        let bbox = self.bounding_box();
+        if bbox.is_bounded() {
+            bbox
+        } else {
+            Aabb::EMPTY
+        }
+    }
+}
+
+impl Hittable for Box<dyn Hittable> {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        self.as_ref().hit(ray, t_range)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.as_ref().bounding_box()
+    }
+
+    fn bounded_box(&self) -> Aabb {
+        self.as_ref().bounded_box()
+    }
 }
 
 pub struct HittableVec<T: Hittable> {
@@ -58,17 +105,88 @@ impl<T: Hittable> HittableVec<T> {
         Self { vec }
     }
 
-    pub fn hit_by(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut closest = t_max;
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// Objects whose bounding box intersects `region`
+    pub fn objects_in_box(&self, region: &Aabb) -> Vec<&T> {
+        self.vec
+            .iter()
+            .filter(|item| item.bounding_box().overlaps(region))
+            .collect()
+    }
+}
+
+/// Objects of any kind
+pub type World = HittableVec<Box<dyn Hittable>>;
+
+impl<T: Hittable> Hittable for HittableVec<T> {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        crate::stats::count_intersection_tests(self.vec.len() as u64);
+        let mut closest = t_range.max;
         let mut hit: Option<HitRecord> = None;
         for item in &self.vec {
-            if let Some(h) = item.hit_by(ray, t_min, closest) {
+            if let Some(h) = item.hit(ray, Interval::new(t_range.min, closest)) {
                 closest = h.t;
                 hit = Some(h);
             }
         }
         hit
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.vec.iter().fold(Aabb::EMPTY, |bbox, item| {
+            Aabb::surrounding(&bbox, &item.bounding_box())
+        })
+    }
+
+    fn bounded_box(&self) -> Aabb {
+        self.vec.iter().fold(Aabb::EMPTY, |bbox, item| {
+            Aabb::surrounding(&bbox, &item.bounded_box())
+        })
+    }
 }
 
-pub const T_INFINITY: f64 = f64::MAX;
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::test::gray;
+    use crate::plane::Plane;
+    use crate::sphere::Sphere;
+    #[test]
+    fn objects_overlapping_query_box() {
+        let sphere = |x: f64| Sphere::new(Point::new(x, 0.0, 0.0), 1.0, gray());
+        let world = HittableVec::new(vec![sphere(0.0), sphere(3.0), sphere(10.0)]);
+        let region = Aabb::from_points(&Point::new(0.5, -0.5, -0.5), &Point::new(2.5, 0.5, 0.5));
+        let found = world.objects_in_box(&region);
+        assert_eq!(2, found.len());
+        assert_eq!(Point::new(0.0, 0.0, 0.0), found[0].center);
+        assert_eq!(Point::new(3.0, 0.0, 0.0), found[1].center);
+    }
+
+    #[test]
+    fn world_mixes_object_kinds() {
+        let world: World = HittableVec::new(vec![
+            Box::new(Sphere::new(Point::new(0.0, 0.0, -3.0), 1.0, gray())),
+            Box::new(Plane::new(
+                Point::new(0.0, -1.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+                gray(),
+            )),
+        ]);
+        let ahead = Interval::new(0.001, f64::MAX);
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let forward = world.hit(&Ray::new(origin, Vector::new(0.0, 0.0, -1.0)), ahead);
+        assert_eq!(2.0, forward.unwrap().t);
+        let down = world.hit(&Ray::new(origin, Vector::new(0.0, -1.0, 0.0)), ahead);
+        assert_eq!(Point::new(0.0, -1.0, 0.0), down.unwrap().point);
+        assert!(world
+            .hit(&Ray::new(origin, Vector::new(0.0, 1.0, 0.0)), ahead)
+            .is_none());
+    }
+}
@@ -0,0 +1,183 @@
+use crate::image::{colors, Color, Image};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+#[derive(Debug, Clone, Copy)]
+struct PixelStats {
+    count: u32,
+    sum: Color,
+    // luminance moments, used to estimate the variance
+    lum_sum: f64,
+    lum_sum_sq: f64,
+}
+
+impl PixelStats {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            sum: colors::BLACK,
+            lum_sum: 0.0,
+            lum_sum_sq: 0.0,
+        }
+    }
+
+    fn add(&mut self, color: Color) {
+        let lum = (color.red + color.green + color.blue) / 3.0;
+        self.count += 1;
+        self.sum += color;
+        self.lum_sum += lum;
+        self.lum_sum_sq += lum * lum;
+    }
+
+    fn mean(&self) -> Color {
+        &self.sum / self.count as f64
+    }
+
+    // estimated variance of the pixel mean
+    fn mean_variance(&self) -> f64 {
+        if self.count < 2 {
+            return f64::MAX;
+        }
+        let n = self.count as f64;
+        let mean = self.lum_sum / n;
+        let variance = (self.lum_sum_sq / n - mean * mean).max(0.0) * n / (n - 1.0);
+        variance / n
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Priority {
+    variance: f64,
+    index: usize,
+}
+
+impl Eq for Priority {}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.variance
+            .partial_cmp(&other.variance)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+pub struct Refinement {
+    pub image: Image,
+    /// Number of samples taken for each pixel
+    pub samples: Vec<u32>,
+}
+
+/// Render `width` x `height` pixels with `initial` samples each, then spend
+/// `extra` samples per pixel on average where the estimated variance is
+/// the highest. `sample(col, line)` draws a single sample of a pixel.
+pub fn progressive_refine<F: FnMut(usize, usize) -> Color>(
+    width: usize,
+    height: usize,
+    initial: u32,
+    extra: u32,
+    mut sample: F,
+) -> Refinement {
+    let initial = initial.max(1);
+    let mut stats = vec![PixelStats::new(); width * height];
+    let mut queue = BinaryHeap::with_capacity(stats.len());
+    for (index, px) in stats.iter_mut().enumerate() {
+        for _ in 0..initial {
+            px.add(sample(index % width, index / width));
+        }
+        queue.push(Priority {
+            variance: px.mean_variance(),
+            index,
+        });
+    }
+    // refine the noisiest pixel by batches of the initial sample count
+    let mut remaining = extra as usize * stats.len();
+    while remaining > 0 {
+        let index = match queue.pop() {
+            Some(top) => top.index,
+            None => break,
+        };
+        let batch = remaining.min(initial as usize);
+        let px = &mut stats[index];
+        for _ in 0..batch {
+            px.add(sample(index % width, index / width));
+        }
+        remaining -= batch;
+        queue.push(Priority {
+            variance: px.mean_variance(),
+            index,
+        });
+    }
+    let mut image = Image::new(width, height);
+    for (data, px) in image.data.iter_mut().zip(&stats) {
+        *data = px.mean();
+    }
+    Refinement {
+        image,
+        samples: stats.iter().map(|px| px.count).collect(),
+    }
+}
+
+/// Grayscale image of the per-pixel sample counts, normalized by the max count
+pub fn sample_map(samples: &[u32], width: usize, height: usize) -> Image {
+    let max = samples.iter().copied().max().unwrap_or(0).max(1) as f64;
+    let mut image = Image::new(width, height);
+    for (px, &count) in image.data.iter_mut().zip(samples) {
+        let val = count as f64 / max;
+        *px = Color::new(val, val, val);
+    }
+    image
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::Rng;
+    #[test]
+    fn noisy_region_gets_more_samples() {
+        let mut rng = rand::thread_rng();
+        // left half is noisy, right half is flat
+        let refined = progressive_refine(8, 4, 4, 8, |col, _| {
+            if col < 4 {
+                let val = rng.gen_range(0.0, 1.0);
+                Color::new(val, val, val)
+            } else {
+                Color::new(0.5, 0.5, 0.5)
+            }
+        });
+        let (mut noisy, mut flat) = (0, 0);
+        for (index, count) in refined.samples.iter().enumerate() {
+            if index % 8 < 4 {
+                noisy += count;
+            } else {
+                flat += count;
+            }
+        }
+        assert_eq!(8 * 4 * (4 + 8), noisy + flat);
+        assert!(noisy > 4 * flat);
+        assert_eq!(0.5, refined.image.data[7].red);
+    }
+
+    #[test]
+    fn sample_map_is_brighter_on_edges() {
+        let mut rng = rand::thread_rng();
+        // vertical edge between black and white inside pixels of column 3
+        let refined = progressive_refine(8, 4, 16, 4, |col, _| {
+            let x = col as f64 + rng.gen_range(0.0, 1.0);
+            let val = if x < 3.5 { 0.0 } else { 1.0 };
+            Color::new(val, val, val)
+        });
+        let map = sample_map(&refined.samples, 8, 4);
+        for line in 0..4 {
+            let edge = map.data[line * 8 + 3].red;
+            assert!(map.data[line * 8].red < edge);
+            assert!(map.data[line * 8 + 7].red < edge);
+        }
+    }
+}
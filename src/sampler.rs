@@ -0,0 +1,194 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Strategy placing the samples of a pixel in the unit square
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampler {
+    /// Independent uniform samples
+    Random,
+    /// One jittered sample per cell of a square grid, independent uniform
+    /// samples when their number isn't a perfect square
+    Stratified,
+    /// Halton sequence of bases 2 and 3
+    Halton,
+    /// Sobol sequence, first two dimensions
+    Sobol,
+}
+
+impl std::str::FromStr for Sampler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Sampler::Random),
+            "stratified" => Ok(Sampler::Stratified),
+            "halton" => Ok(Sampler::Halton),
+            "sobol" => Ok(Sampler::Sobol),
+            _ => Err(format!("Unknown sampler {}", s)),
+        }
+    }
+}
+
+/// Digits of `index` in `base` mirrored around the radix point
+pub fn radical_inverse(base: u32, mut index: u32) -> f64 {
+    let (mut result, mut scale) = (0.0, 1.0 / base as f64);
+    while index > 0 {
+        result += (index % base) as f64 * scale;
+        index /= base;
+        scale /= base as f64;
+    }
+    result
+}
+
+pub fn halton(index: u32) -> (f64, f64) {
+    // index 0 would be the origin for every base
+    (radical_inverse(2, index + 1), radical_inverse(3, index + 1))
+}
+
+pub fn sobol(index: u32) -> (f64, f64) {
+    // first dimension is van der Corput in base 2, direction numbers of
+    // the second one come from the primitive polynomial x + 1
+    let (mut first, mut second) = (0u32, 0u32);
+    let mut direction = 1u32 << 31;
+    for bit in 0..32 {
+        if index >> bit & 1 == 1 {
+            first ^= 1 << (31 - bit);
+            second ^= direction;
+        }
+        direction ^= direction >> 1;
+    }
+    let scale = 1.0 / (1u64 << 32) as f64;
+    (first as f64 * scale, second as f64 * scale)
+}
+
+impl Sampler {
+    /// `count` points of the unit square for a single pixel. Sequences are
+    /// shifted by a random offset, wrapping around, so that pixels don't
+    /// share the same points.
+    pub fn pixel_samples<R: Rng>(&self, count: usize, rng: &mut R) -> Vec<(f64, f64)> {
+        let mut uniform = || (rng.gen_range(0.0, 1.0), rng.gen_range(0.0, 1.0));
+        let shift = uniform();
+        let rotate = |(x, y): (f64, f64)| ((x + shift.0) % 1.0, (y + shift.1) % 1.0);
+        match self {
+            Sampler::Random => (0..count).map(|_| uniform()).collect(),
+            Sampler::Stratified => {
+                let n = (count as f64).sqrt().round() as usize;
+                if n * n != count {
+                    return (0..count).map(|_| uniform()).collect();
+                }
+                (0..count)
+                    .map(|i| {
+                        let (dx, dy) = uniform();
+                        (
+                            ((i % n) as f64 + dx) / n as f64,
+                            ((i / n) as f64 + dy) / n as f64,
+                        )
+                    })
+                    .collect()
+            }
+            Sampler::Halton => (0..count as u32).map(|i| rotate(halton(i))).collect(),
+            Sampler::Sobol => (0..count as u32).map(|i| rotate(sobol(i))).collect(),
+        }
+    }
+
+    /// Pixel samples in an order unrelated to the one of `pixel_samples`,
+    /// for the lens dimensions
+    pub fn lens_samples<R: Rng>(&self, count: usize, rng: &mut R) -> Vec<(f64, f64)> {
+        let mut samples = self.pixel_samples(count, rng);
+        samples.shuffle(rng);
+        samples
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // largest difference between the fraction of points in [0, x) x [0, y)
+    // and its area, over a grid of boxes
+    fn star_discrepancy(points: &[(f64, f64)]) -> f64 {
+        let mut worst: f64 = 0.0;
+        for i in 1..=32 {
+            for j in 1..=32 {
+                let (x, y) = (i as f64 / 32.0, j as f64 / 32.0);
+                let inside = points.iter().filter(|p| p.0 < x && p.1 < y).count();
+                worst = worst.max((inside as f64 / points.len() as f64 - x * y).abs());
+            }
+        }
+        worst
+    }
+
+    #[test]
+    fn radical_inverse_examples() {
+        assert_eq!(0.5, radical_inverse(2, 1));
+        assert_eq!(0.25, radical_inverse(2, 2));
+        assert_eq!(0.75, radical_inverse(2, 3));
+        assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-12);
+        assert_eq!((0.0, 0.0), sobol(0));
+        assert_eq!((0.5, 0.5), sobol(1));
+        assert_eq!((0.25, 0.75), sobol(2));
+        assert_eq!((0.75, 0.25), sobol(3));
+    }
+
+    #[test]
+    fn low_discrepancy_beats_random() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let random = star_discrepancy(&Sampler::Random.pixel_samples(256, &mut rng));
+        let unshifted: Vec<_> = (0..256).map(halton).collect();
+        assert!(star_discrepancy(&unshifted) < random / 2.0);
+        let shifted = Sampler::Halton.pixel_samples(256, &mut rng);
+        assert!(star_discrepancy(&shifted) < random);
+        let shifted = Sampler::Sobol.pixel_samples(256, &mut rng);
+        assert!(star_discrepancy(&shifted) < random);
+    }
+
+    #[test]
+    fn pixels_get_different_points() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for sampler in &[Sampler::Stratified, Sampler::Halton, Sampler::Sobol] {
+            let first = sampler.pixel_samples(16, &mut rng);
+            let second = sampler.pixel_samples(16, &mut rng);
+            assert_eq!(16, first.len());
+            assert_ne!(first, second);
+            for &(x, y) in first.iter().chain(&second) {
+                assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+            }
+        }
+    }
+
+    // variance over many pixels of the mean of their samples
+    fn mean_variance(sampler: Sampler, count: usize) -> f64 {
+        let mut rng = StdRng::seed_from_u64(11);
+        let means: Vec<(f64, f64)> = (0..2000)
+            .map(|_| {
+                let samples = sampler.pixel_samples(count, &mut rng);
+                let sum = samples
+                    .iter()
+                    .fold((0.0, 0.0), |acc, p| (acc.0 + p.0, acc.1 + p.1));
+                (sum.0 / count as f64, sum.1 / count as f64)
+            })
+            .collect();
+        let variance = |axis: fn(&(f64, f64)) -> f64| {
+            let mean = means.iter().map(axis).sum::<f64>() / means.len() as f64;
+            means.iter().map(|p| (axis(p) - mean).powi(2)).sum::<f64>() / means.len() as f64
+        };
+        variance(|p| p.0) + variance(|p| p.1)
+    }
+
+    #[test]
+    fn stratified_means_vary_less() {
+        let random = mean_variance(Sampler::Random, 16);
+        let stratified = mean_variance(Sampler::Stratified, 16);
+        // in theory 16 times less, one sample per cell of a 4x4 grid
+        assert!(stratified < random / 8.0);
+        // 15 isn't a perfect square
+        let mut a = StdRng::seed_from_u64(2);
+        let mut b = StdRng::seed_from_u64(2);
+        assert_eq!(
+            Sampler::Random.pixel_samples(15, &mut a),
+            Sampler::Stratified.pixel_samples(15, &mut b)
+        );
+    }
+}
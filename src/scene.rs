@@ -0,0 +1,534 @@
+use crate::background::{Background, GradientSky, SolidBackground};
+use crate::box_prim::BoxPrim;
+use crate::capsule::Capsule;
+use crate::cylinder::Cylinder;
+use crate::disk::Disk;
+use crate::error::RenderError;
+use crate::image::Color;
+use crate::instance::{RotateY, Translate};
+use crate::light::PointLight;
+use crate::material::{Dielectric, Isotropic, Lambertian, Material, Metal};
+use crate::medium::ConstantMedium;
+use crate::obj;
+use crate::plane::Plane;
+use crate::ray::{Hittable, HittableVec, World};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::vec::{Point, Vector};
+use crate::{Camera, RenderSettings};
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::path::Path;
+
+/// Color written as a linear RGB triplet or an sRGB hexadecimal code
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Rgb([f64; 3]),
+    Hex(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "ColorValue")]
+struct ColorDesc(Color);
+
+impl TryFrom<ColorValue> for ColorDesc {
+    type Error = String;
+
+    fn try_from(value: ColorValue) -> Result<Self, Self::Error> {
+        match value {
+            ColorValue::Rgb(c) => Ok(ColorDesc(Color::new(c[0], c[1], c[2]))),
+            ColorValue::Hex(code) => Color::from_hex(&code).map(ColorDesc),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum MaterialDesc {
+    Lambertian { albedo: ColorDesc },
+    Metal { albedo: ColorDesc, fuzz: f64 },
+    Dielectric { refraction_index: f64 },
+}
+
+impl MaterialDesc {
+    fn build(&self) -> Box<dyn Material> {
+        match self {
+            MaterialDesc::Lambertian { albedo } => Box::new(Lambertian::new(albedo.0)),
+            MaterialDesc::Metal { albedo, fuzz } => Box::new(Metal::new(albedo.0, *fuzz)),
+            MaterialDesc::Dielectric { refraction_index } => {
+                Box::new(Dielectric::new(*refraction_index))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BackgroundDesc {
+    Gradient { top: ColorDesc, bottom: ColorDesc },
+    Solid { color: ColorDesc },
+}
+
+impl BackgroundDesc {
+    fn build(&self) -> Box<dyn Background> {
+        match self {
+            BackgroundDesc::Gradient { top, bottom } => Box::new(GradientSky {
+                top: top.0,
+                bottom: bottom.0,
+            }),
+            BackgroundDesc::Solid { color } => Box::new(SolidBackground(color.0)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SphereDesc {
+    center: [f64; 3],
+    /// reached at the end of the motion, spheres without it stay still
+    center1: Option<[f64; 3]>,
+    radius: f64,
+    material: MaterialDesc,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaneDesc {
+    point: [f64; 3],
+    normal: [f64; 3],
+    material: MaterialDesc,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiskDesc {
+    center: [f64; 3],
+    normal: [f64; 3],
+    radius: f64,
+    material: MaterialDesc,
+}
+
+/// Rotation of an object around the y axis, then translation
+#[derive(Debug, Deserialize)]
+struct TransformDesc {
+    /// degrees, counterclockwise seen from above
+    rotate_y: Option<f64>,
+    translate: Option<[f64; 3]>,
+}
+
+impl TransformDesc {
+    fn apply(&self, object: Box<dyn Hittable>) -> Box<dyn Hittable> {
+        let object = match self.rotate_y {
+            Some(degrees) => Box::new(RotateY::new(object, degrees)),
+            None => object,
+        };
+        match self.translate {
+            Some(offset) => Box::new(Translate::new(object, Vector::from(offset))),
+            None => object,
+        }
+    }
+}
+
+// `object` moved by `transform`, if any
+fn place(object: Box<dyn Hittable>, transform: &Option<TransformDesc>) -> Box<dyn Hittable> {
+    match transform {
+        Some(transform) => transform.apply(object),
+        None => object,
+    }
+}
+
+/// Axis-aligned box, unless transformed
+#[derive(Debug, Deserialize)]
+struct BoxDesc {
+    min: [f64; 3],
+    max: [f64; 3],
+    material: MaterialDesc,
+    transform: Option<TransformDesc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CylinderDesc {
+    /// center of the bottom cap
+    base: [f64; 3],
+    axis: [f64; 3],
+    radius: f64,
+    height: f64,
+    material: MaterialDesc,
+}
+
+/// Cylinder of radius `radius` around the segment from `a` to `b`, capped
+/// with half spheres
+#[derive(Debug, Deserialize)]
+struct CapsuleDesc {
+    a: [f64; 3],
+    b: [f64; 3],
+    radius: f64,
+    material: MaterialDesc,
+}
+
+/// Shape enclosing a medium, it is not drawn itself
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BoundaryDesc {
+    Sphere { center: [f64; 3], radius: f64 },
+    Box { min: [f64; 3], max: [f64; 3] },
+}
+
+/// Fog or smoke filling a boundary
+#[derive(Debug, Deserialize)]
+struct MediumDesc {
+    boundary: BoundaryDesc,
+    density: f64,
+    color: ColorDesc,
+}
+
+impl MediumDesc {
+    fn build(&self) -> ConstantMedium {
+        let point = |p: &[f64; 3]| Point::from(*p);
+        let phase = || Box::new(Isotropic::new(self.color.0));
+        let boundary: Box<dyn Hittable> = match &self.boundary {
+            BoundaryDesc::Sphere { center, radius } => {
+                Box::new(Sphere::new(point(center), *radius, phase()))
+            }
+            BoundaryDesc::Box { min, max } => {
+                Box::new(BoxPrim::new(point(min), point(max), phase()))
+            }
+        };
+        ConstantMedium::new(boundary, self.density, phase())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MeshDesc {
+    /// OBJ file, relative to the scene file
+    path: String,
+    material: MaterialDesc,
+    transform: Option<TransformDesc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LightDesc {
+    position: [f64; 3],
+    intensity: ColorDesc,
+}
+
+#[derive(Debug, Deserialize)]
+struct CameraDesc {
+    look_from: [f64; 3],
+    look_at: [f64; 3],
+    /// defaults to +y
+    vup: Option<[f64; 3]>,
+    /// vertical field of view, in degrees
+    vertical_fov: f64,
+    /// defaults to a pinhole
+    aperture: Option<f64>,
+    /// defaults to the distance to `look_at`
+    focus_distance: Option<f64>,
+}
+
+/// Render settings given by a scene, options given on the command line take
+/// precedence
+#[derive(Debug, Default, Deserialize)]
+pub struct SceneSettings {
+    samples: Option<u16>,
+    glass_bounces: Option<u16>,
+    diffuse_bounces: Option<u16>,
+    /// defaults to the blue sky gradient
+    background: Option<BackgroundDesc>,
+    /// sample the lights at each diffuse hit, off by default
+    direct_lighting: Option<bool>,
+}
+
+impl SceneSettings {
+    pub fn apply(&self, settings: &mut RenderSettings) {
+        if let Some(samples) = self.samples {
+            settings.aa_samples(samples);
+        }
+        if let Some(bounces) = self.glass_bounces {
+            settings.glass_bounces(bounces);
+        }
+        if let Some(bounces) = self.diffuse_bounces {
+            settings.diffuse_bounces(bounces);
+        }
+        if let Some(direct) = self.direct_lighting {
+            settings.direct_lighting(direct);
+        }
+        if let Some(background) = &self.background {
+            settings.background(background.build());
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SceneDesc {
+    camera: CameraDesc,
+    settings: Option<SceneSettings>,
+    spheres: Vec<SphereDesc>,
+    planes: Option<Vec<PlaneDesc>>,
+    disks: Option<Vec<DiskDesc>>,
+    boxes: Option<Vec<BoxDesc>>,
+    cylinders: Option<Vec<CylinderDesc>>,
+    capsules: Option<Vec<CapsuleDesc>>,
+    media: Option<Vec<MediumDesc>>,
+    meshes: Option<Vec<MeshDesc>>,
+    /// point lights, only used with direct lighting
+    lights: Option<Vec<LightDesc>>,
+}
+
+/// World, camera and settings read from a JSON description
+pub struct Scene {
+    pub world: World,
+    pub camera: Camera,
+    pub lights: Vec<PointLight>,
+    pub settings: SceneSettings,
+}
+
+impl Scene {
+    /// Read the scene at `path`, framed for images of `aspect_ratio`
+    pub fn from_json(path: &str, aspect_ratio: f64) -> Result<Scene, RenderError> {
+        let json = std::fs::read_to_string(path)?;
+        let desc: SceneDesc = serde_json::from_str(&json)
+            .map_err(|e| RenderError::InvalidScene(format!("{}: {}", path, e)))?;
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        Scene::build(desc, aspect_ratio, dir)
+    }
+
+    // `dir` is the directory of the scene file
+    fn build(desc: SceneDesc, aspect_ratio: f64, dir: &Path) -> Result<Scene, RenderError> {
+        let point = |p: &[f64; 3]| Point::from(*p);
+        let spheres = desc.spheres.iter().map(|s| match &s.center1 {
+            Some(center1) => Box::new(MovingSphere::new(
+                point(&s.center),
+                point(center1),
+                0.0,
+                1.0,
+                s.radius,
+                s.material.build(),
+            )) as Box<dyn Hittable>,
+            None => Box::new(Sphere::new(point(&s.center), s.radius, s.material.build())),
+        });
+        let planes = desc.planes.iter().flatten().map(|p| {
+            let plane = Plane::new(point(&p.point), point(&p.normal), p.material.build());
+            Box::new(plane) as Box<dyn Hittable>
+        });
+        let disks = desc.disks.iter().flatten().map(|d| {
+            let disk = Disk::new(
+                point(&d.center),
+                point(&d.normal),
+                d.radius,
+                d.material.build(),
+            );
+            Box::new(disk) as Box<dyn Hittable>
+        });
+        let boxes = desc.boxes.iter().flatten().map(|b| {
+            let box_prim = BoxPrim::new(point(&b.min), point(&b.max), b.material.build());
+            place(Box::new(box_prim), &b.transform)
+        });
+        let cylinders = desc.cylinders.iter().flatten().map(|c| {
+            let cylinder = Cylinder::new(
+                point(&c.base),
+                point(&c.axis),
+                c.radius,
+                c.height,
+                c.material.build(),
+            );
+            Box::new(cylinder) as Box<dyn Hittable>
+        });
+        let capsules = desc.capsules.iter().flatten().map(|c| {
+            let capsule = Capsule::new(point(&c.a), point(&c.b), c.radius, c.material.build());
+            Box::new(capsule) as Box<dyn Hittable>
+        });
+        let media = desc
+            .media
+            .iter()
+            .flatten()
+            .map(|m| Box::new(m.build()) as Box<dyn Hittable>);
+        let mut meshes = Vec::new();
+        for m in desc.meshes.iter().flatten() {
+            let path = dir.join(&m.path);
+            let triangles = obj::load_obj(&path.to_string_lossy(), m.material.build())?;
+            meshes.push(place(Box::new(HittableVec::new(triangles)), &m.transform));
+        }
+        let lights = desc
+            .lights
+            .iter()
+            .flatten()
+            .map(|l| PointLight::new(point(&l.position), l.intensity.0))
+            .collect();
+        let cam = &desc.camera;
+        let (look_from, look_at) = (point(&cam.look_from), point(&cam.look_at));
+        let camera = Camera::new(
+            look_from,
+            look_at,
+            cam.vup.as_ref().map_or(Point::new(0.0, 1.0, 0.0), point),
+            cam.vertical_fov,
+            aspect_ratio,
+            1.0,
+            cam.aperture.unwrap_or(0.0),
+            cam.focus_distance
+                .unwrap_or_else(|| (look_at - look_from).length()),
+        );
+        Ok(Scene {
+            world: HittableVec::new(
+                spheres
+                    .chain(planes)
+                    .chain(disks)
+                    .chain(boxes)
+                    .chain(cylinders)
+                    .chain(capsules)
+                    .chain(media)
+                    .chain(meshes)
+                    .collect(),
+            ),
+            camera,
+            lights,
+            settings: desc.settings.unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[test]
+    fn sample_scene_is_loaded() {
+        let scene = Scene::from_json(&fixture("three_spheres.json"), 1.5).unwrap();
+        assert_eq!(4, scene.world.len());
+        assert_eq!(Point::new(13.0, 2.0, 3.0), scene.camera.position);
+        let mut settings = RenderSettings::default();
+        settings.glass_bounces(50).diffuse_bounces(50);
+        scene.settings.apply(&mut settings);
+        assert_eq!(16, settings.antialiasing_samples);
+        assert_eq!(8, settings.bounces.diffuse);
+        // not given by the scene
+        assert_eq!(50, settings.bounces.glass);
+    }
+
+    #[test]
+    fn invalid_scenes_are_reported() {
+        let err = |path: &str| match Scene::from_json(path, 1.5) {
+            Err(e) => e,
+            Ok(_) => panic!("{} loaded", path),
+        };
+        assert!(matches!(err("missing.json"), RenderError::Io(_)));
+        let path =
+            std::env::temp_dir().join(format!("raytracer-{}-scene.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(
+            path,
+            r#"{"camera": {"look_from": [0, 0, 0]}, "spheres": []}"#,
+        )
+        .unwrap();
+        let e = err(path);
+        assert!(matches!(e, RenderError::InvalidScene(_)));
+        std::fs::write(
+            path,
+            r##"{
+                "camera": {"look_from": [0, 0, 1], "look_at": [0, 0, 0], "vertical_fov": 40},
+                "spheres": [{
+                    "center": [0, 0, 0],
+                    "radius": 1,
+                    "material": {"type": "lambertian", "albedo": "#12345"}
+                }]
+            }"##,
+        )
+        .unwrap();
+        let e = err(path);
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(e, RenderError::InvalidScene(_)));
+    }
+
+    // point hit by a ray cast from `origin` toward -z
+    fn hit_toward_minus_z(world: &World, origin: Point) -> Option<Point> {
+        let ray = crate::ray::Ray::new(origin, crate::vec::Vector::new(0.0, 0.0, -1.0));
+        world
+            .hit(&ray, crate::interval::Interval::new(0.001, f64::MAX))
+            .map(|hit| hit.point)
+    }
+
+    #[test]
+    fn primitives_are_loaded() {
+        let scene = Scene::from_json(&fixture("primitives.json"), 1.0).unwrap();
+        assert_eq!(7, scene.world.len());
+        // each object is in its own column along x
+        let disk = hit_toward_minus_z(&scene.world, Point::new(0.0, 0.0, 5.0));
+        assert_eq!(Some(Point::new(0.0, 0.0, -1.0)), disk);
+        assert!(hit_toward_minus_z(&scene.world, Point::new(0.0, 1.5, 5.0)).is_none());
+        let box_prim = hit_toward_minus_z(&scene.world, Point::new(3.0, 0.0, 5.0));
+        assert_eq!(Some(Point::new(3.0, 0.0, -0.5)), box_prim);
+        let cylinder = hit_toward_minus_z(&scene.world, Point::new(6.0, 0.0, 5.0));
+        assert_eq!(Some(Point::new(6.0, 0.0, -0.5)), cylinder);
+        let capsule = hit_toward_minus_z(&scene.world, Point::new(9.0, 0.0, 5.0));
+        assert_eq!(Some(Point::new(9.0, 0.0, -0.5)), capsule);
+        // so dense that rays scatter as soon as they enter
+        let fog = hit_toward_minus_z(&scene.world, Point::new(12.0, 0.0, 5.0)).unwrap();
+        assert!(fog.approx_eq(&Point::new(12.0, 0.0, -0.5), 1e-3));
+        // a unit cube turned by 45 degrees shows an edge
+        let turned = hit_toward_minus_z(&scene.world, Point::new(15.0, 0.0, 5.0)).unwrap();
+        assert!(turned.approx_eq(&Point::new(15.0, 0.0, 0.5f64.sqrt() - 1.0), 1e-9));
+        // a sphere going down by 2 over the motion
+        let moving = hit_toward_minus_z(&scene.world, Point::new(18.0, 0.0, 5.0));
+        assert_eq!(Some(Point::new(18.0, 0.0, -0.5)), moving);
+        let late = crate::ray::Ray::timed(
+            Point::new(18.0, -2.0, 5.0),
+            crate::vec::Vector::new(0.0, 0.0, -1.0),
+            1.0,
+        );
+        let hit = scene
+            .world
+            .hit(&late, crate::interval::Interval::new(0.001, f64::MAX))
+            .unwrap();
+        assert_eq!(Point::new(18.0, -2.0, -0.5), hit.point);
+    }
+
+    #[test]
+    fn meshes_are_loaded_next_to_the_scene() {
+        let scene = Scene::from_json(&fixture("quad_scene.json"), 1.0).unwrap();
+        assert_eq!(1, scene.world.len());
+        let ray = crate::ray::Ray::new(
+            Point::new(0.5, -0.5, 2.0),
+            crate::vec::Vector::new(0.0, 0.0, -1.0),
+        );
+        let hit = scene
+            .world
+            .hit(&ray, crate::interval::Interval::new(0.001, f64::MAX))
+            .unwrap();
+        assert_eq!(2.0, hit.t);
+    }
+
+    #[test]
+    fn background_is_chosen_by_the_scene() {
+        let path =
+            std::env::temp_dir().join(format!("raytracer-{}-night.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(
+            path,
+            r##"{
+                "camera": {"look_from": [0, 0, 1], "look_at": [0, 0, 0], "vertical_fov": 40},
+                "settings": {"background": {"type": "solid", "color": "#000059"}},
+                "spheres": [],
+                "planes": [{
+                    "point": [0, -1, 0],
+                    "normal": [0, 1, 0],
+                    "material": {"type": "lambertian", "albedo": [0.5, 0.5, 0.5]}
+                }]
+            }"##,
+        )
+        .unwrap();
+        let scene = Scene::from_json(path, 1.0);
+        std::fs::remove_file(path).unwrap();
+        let scene = scene.unwrap();
+        assert_eq!(1, scene.world.len());
+        let mut settings = RenderSettings::default();
+        scene.settings.apply(&mut settings);
+        let ray = crate::ray::Ray::new(
+            Point::new(0.0, 0.0, 0.0),
+            crate::vec::Vector::new(0.0, 1.0, 0.0),
+        );
+        let c = settings.background.sample(&ray);
+        assert_eq!((0.0, 0.0), (c.red, c.green));
+        assert!((c.blue - 0.1).abs() < 1e-3);
+    }
+}
@@ -1,6 +1,8 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
 use crate::material::Material;
 use crate::ray::{HitRecord, Hittable, Ray};
-use crate::vec::{self, Point};
+use crate::vec::{self, Point, Vector};
 
 #[derive(Debug)]
 pub struct Sphere {
@@ -19,52 +21,209 @@ impl Sphere {
     }
 }
 
-impl Hittable for Sphere {
-    fn hit_by(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        // let S be a sphere of center C and radius r
-        // a point P is on the sphere if ||P - C||² = r²
-        // a vector V has ||V||² = V.V
-        // a ray R with origin O and direction D hits the sphere
-        // if for any t ||O + tD - C||² = r²
-        //               (O + tD - C).(O + tD - C) = r²
-        // which means t²||D||² + 2tD.(O - C) + ||O - C||² - r² = 0
-        // This is a quadratic equation with
-        // a = ||D||²
-        // b = 2D.(O-C)
-        // c = ||O - C||² - r²
-        // discriminant d is b² - 4ac
-        // if negative, no real solution exist so no intersection
-        // if 0, a single solution exists -b / 2a
-        // if positive, 2 solutions exist (-b +- sqrt(d)) / 2a
-        let oc = ray.origin - self.center;
-        let a = ray.direction.length_squared();
-        // b has a factor 2 so let b = 2h
-        // the quadratic equation is t = (-b +- sqrt(b² - 4ac)) / 2a
-        // replacing b gives (-2h +- sqrt((2h)² - 4ac)) / 2a
-        // then              (-h +- sqrt(h² - ac)) / a
-        let half_b = vec::dot(&ray.direction, &oc);
-        let c = oc.length_squared() - self.radius * self.radius;
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            None
-        } else {
-            let discr_sqrt = discriminant.sqrt();
-            let mut t = (-half_b - discr_sqrt) / a;
-            fn within_range(t: f64, min: f64, max: f64) -> bool {
-                t > min && t < max
-            }
-            if !within_range(t, t_min, t_max) {
-                t = (-half_b + discr_sqrt) / a;
-                if !within_range(t, t_min, t_max) {
-                    return None;
-                }
+/// Surface coordinates of the point of the unit sphere at `normal`: u is the
+/// angle around the y axis from -x, v the angle from -y, both normalized
+fn sphere_uv(normal: &Vector) -> (f64, f64) {
+    use std::f64::consts::PI;
+    let u = ((-normal.z).atan2(normal.x) + PI) / (2.0 * PI);
+    let v = (-normal.y).acos() / PI;
+    (u, v)
+}
+
+/// Intersection of `ray` with the sphere of `center` and `radius` within `t_range`
+fn hit_sphere<'a>(
+    center: Point,
+    radius: f64,
+    material: &'a Box<dyn Material>,
+    ray: &Ray,
+    t_range: Interval,
+) -> Option<HitRecord<'a>> {
+    // let S be a sphere of center C and radius r
+    // a point P is on the sphere if ||P - C||² = r²
+    // a vector V has ||V||² = V.V
+    // a ray R with origin O and direction D hits the sphere
+    // if for any t ||O + tD - C||² = r²
+    //               (O + tD - C).(O + tD - C) = r²
+    // which means t²||D||² + 2tD.(O - C) + ||O - C||² - r² = 0
+    // This is a quadratic equation with
+    // a = ||D||²
+    // b = 2D.(O-C)
+    // c = ||O - C||² - r²
+    // discriminant d is b² - 4ac
+    // if negative, no real solution exist so no intersection
+    // if 0, a single solution exists -b / 2a
+    // if positive, 2 solutions exist (-b +- sqrt(d)) / 2a
+    let oc = ray.origin - center;
+    let a = ray.direction.length_squared();
+    // b has a factor 2 so let b = 2h
+    // the quadratic equation is t = (-b +- sqrt(b² - 4ac)) / 2a
+    // replacing b gives (-2h +- sqrt((2h)² - 4ac)) / 2a
+    // then              (-h +- sqrt(h² - ac)) / a
+    let half_b = vec::dot(&ray.direction, &oc);
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        None
+    } else {
+        let discr_sqrt = discriminant.sqrt();
+        let mut t = (-half_b - discr_sqrt) / a;
+        if !t_range.surrounds(t) {
+            t = (-half_b + discr_sqrt) / a;
+            if !t_range.surrounds(t) {
+                return None;
             }
-            let intersect = ray.at(t);
-            let normal = (intersect - self.center) / self.radius;
-            // ray direction and normal point the same way if dot product is positive
-            let normal_ray_dot = vec::dot(&normal, &ray.direction);
-            let front = if normal_ray_dot < 0.0 { true } else { false };
-            Some(HitRecord::new(intersect, normal, t, front, &self.material))
         }
+        let intersect = ray.at(t);
+        let normal = (intersect - center) / radius;
+        // ray direction and normal point the same way if dot product is positive
+        let front = vec::dot(&ray.direction, &normal) < 0.0;
+        let (u, v) = sphere_uv(&normal);
+        Some(HitRecord::new(intersect, normal, t, front, material).with_uv(u, v))
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        hit_sphere(self.center, self.radius, &self.material, ray, t_range)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vector::splat(self.radius);
+        Aabb::from_points(&(self.center - r), &(self.center + r))
+    }
+}
+
+/// Sphere moving in a straight line from `center0` at `time0` to `center1`
+/// at `time1`
+#[derive(Debug)]
+pub struct MovingSphere {
+    pub center0: Point,
+    pub center1: Point,
+    pub time0: f64,
+    pub time1: f64,
+    pub radius: f64,
+    pub material: Box<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Point,
+        center1: Point,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Box<dyn Material>,
+    ) -> Self {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    /// Center at `time`, extrapolated outside of [time0, time1]
+    pub fn center(&self, time: f64) -> Point {
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        let progress = (time - self.time0) / (self.time1 - self.time0);
+        self.center0.lerp_unclamped(&self.center1, progress)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        hit_sphere(
+            self.center(ray.time),
+            self.radius,
+            &self.material,
+            ray,
+            t_range,
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // spheres at both ends enclose the whole motion
+        let r = Vector::splat(self.radius);
+        Aabb::surrounding(
+            &Aabb::from_points(&(self.center0 - r), &(self.center0 + r)),
+            &Aabb::from_points(&(self.center1 - r), &(self.center1 + r)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::test::gray;
+    #[test]
+    fn hit_respects_interval_bounds() {
+        let sphere = Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, gray());
+        // enters the sphere at t = 0.5, leaves it at t = 1.5
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let near = sphere.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert_eq!(0.5, near.t);
+        assert!(near.front_face);
+        // bounds are excluded, as with the former (t_min, t_max) version
+        let far = sphere.hit(&ray, Interval::new(0.5, f64::MAX)).unwrap();
+        assert_eq!(1.5, far.t);
+        assert!(!far.front_face);
+        assert!(sphere.hit(&ray, Interval::new(0.5, 1.5)).is_none());
+        assert!(sphere.hit(&ray, Interval::new(0.6, 1.4)).is_none());
+    }
+
+    #[test]
+    fn uv_on_equator_and_poles() {
+        let sphere = Sphere::new(Point::new(0.0, 0.0, 0.0), 2.0, gray());
+        // +x equator
+        let ray = Ray::new(Point::new(5.0, 0.0, 0.0), Vector::new(-1.0, 0.0, 0.0));
+        let hit = sphere.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!((hit.u - 0.5).abs() < 1e-12);
+        assert!((hit.v - 0.5).abs() < 1e-12);
+        let uv = |x, y, z| sphere_uv(&Vector::new(x, y, z));
+        assert_eq!((0.25, 0.5), uv(0.0, 0.0, 1.0));
+        assert_eq!((0.75, 0.5), uv(0.0, 0.0, -1.0));
+        assert_eq!(0.0, uv(0.0, -1.0, 0.0).1);
+        assert_eq!(1.0, uv(0.0, 1.0, 0.0).1);
+    }
+
+    #[test]
+    fn head_on_hit_faces_the_ray() {
+        let sphere = Sphere::new(Point::new(0.0, 0.0, -3.0), 1.0, gray());
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let ray = Ray::new(origin, Vector::new(0.0, 0.0, -1.0));
+        let hit = sphere.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!(hit.front_face);
+        assert_eq!(Vector::new(0.0, 0.0, 1.0), hit.normal);
+        assert!(vec::dot(&hit.normal, &(origin - hit.point)) > 0.0);
+    }
+    #[test]
+    fn moving_sphere_is_hit_where_it_is_at_ray_time() {
+        let sphere = MovingSphere::new(
+            Point::new(0.0, 0.0, -3.0),
+            Point::new(2.0, 0.0, -3.0),
+            0.0,
+            1.0,
+            0.5,
+            gray(),
+        );
+        let ahead = Interval::new(0.001, f64::MAX);
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let direction = Vector::new(0.0, 0.0, -1.0);
+        let start = Ray::timed(origin, direction, 0.0);
+        assert_eq!(2.5, sphere.hit(&start, ahead).unwrap().t);
+        assert!(sphere
+            .hit(&Ray::timed(origin, direction, 1.0), ahead)
+            .is_none());
+        let shifted = Ray::timed(Point::new(2.0, 0.0, 0.0), direction, 1.0);
+        let hit = sphere.hit(&shifted, ahead).unwrap();
+        assert_eq!(Point::new(2.0, 0.0, -2.5), hit.point);
+        assert_eq!(Point::new(1.0, 0.0, -3.0), sphere.center(0.5));
+        let bbox = sphere.bounding_box();
+        assert_eq!(Point::new(-0.5, -0.5, -3.5), bbox.min());
+        assert_eq!(Point::new(2.5, 0.5, -2.5), bbox.max());
     }
 }
@@ -0,0 +1,135 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// totals of all the rendering threads, only ever read at the end
+static RAYS: AtomicU64 = AtomicU64::new(0);
+static INTERSECTION_TESTS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // counted by this thread since its last flush, kept apart so that the
+    // hot loops don't contend on the totals
+    static PENDING_RAYS: Cell<u64> = const { Cell::new(0) };
+    static PENDING_TESTS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Count a ray traced through the world
+pub fn count_ray() {
+    PENDING_RAYS.with(|rays| rays.set(rays.get() + 1));
+}
+
+/// Count `tests` ray-object intersection tests
+pub fn count_intersection_tests(tests: u64) {
+    PENDING_TESTS.with(|count| count.set(count.get() + tests));
+}
+
+/// Add what the current thread counted to the totals, once per chunk of
+/// work such as a line or a tile
+pub fn flush() {
+    RAYS.fetch_add(PENDING_RAYS.with(|rays| rays.replace(0)), Ordering::Relaxed);
+    INTERSECTION_TESTS.fetch_add(
+        PENDING_TESTS.with(|count| count.replace(0)),
+        Ordering::Relaxed,
+    );
+}
+
+/// Rays and intersection tests flushed so far
+pub fn counters() -> (u64, u64) {
+    (
+        RAYS.load(Ordering::Relaxed),
+        INTERSECTION_TESTS.load(Ordering::Relaxed),
+    )
+}
+
+// 1 decimal and a K, M or B suffix from a thousand on
+fn format_count(count: u64) -> String {
+    let count = count as f64;
+    if count >= 1e9 {
+        format!("{:.1}B", count / 1e9)
+    } else if count >= 1e6 {
+        format!("{:.1}M", count / 1e6)
+    } else if count >= 1e3 {
+        format!("{:.1}K", count / 1e3)
+    } else {
+        format!("{}", count)
+    }
+}
+
+/// One line summing up the render of a `width` x `height` image
+pub fn format_summary(
+    width: usize,
+    height: usize,
+    elapsed: Duration,
+    rays: u64,
+    intersection_tests: u64,
+) -> String {
+    let secs = elapsed.as_secs_f64();
+    let rate = if secs > 0.0 {
+        rays as f64 / secs / 1e6
+    } else {
+        0.0
+    };
+    format!(
+        "Rendered {}x{} in {:.1}s, {} rays, {} intersection tests ({:.1} Mrays/s)",
+        width,
+        height,
+        secs,
+        format_count(rays),
+        format_count(intersection_tests),
+        rate
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn summary_formatting() {
+        assert_eq!(
+            "Rendered 1200x800 in 42.3s, 9.8M rays, 4.2B intersection tests (0.2 Mrays/s)",
+            format_summary(
+                1200,
+                800,
+                Duration::from_millis(42_300),
+                9_800_000,
+                4_200_000_000
+            )
+        );
+        assert_eq!(
+            "Rendered 16x8 in 0.5s, 500 rays, 1.5K intersection tests (0.0 Mrays/s)",
+            format_summary(16, 8, Duration::from_millis(500), 500, 1500)
+        );
+        assert_eq!(
+            "Rendered 1x1 in 0.0s, 0 rays, 0 intersection tests (0.0 Mrays/s)",
+            format_summary(1, 1, Duration::from_secs(0), 0, 0)
+        );
+    }
+
+    #[test]
+    fn counters_add_up_across_threads() {
+        let (rays, tests) = counters();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..1000 {
+                        count_ray();
+                        count_intersection_tests(3);
+                    }
+                    flush();
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        // other tests may be rendering at the same time
+        let (more_rays, more_tests) = counters();
+        assert!(more_rays - rays >= 4000);
+        assert!(more_tests - tests >= 12000);
+        // flushing again adds nothing from this thread
+        count_ray();
+        flush();
+        flush();
+        assert_eq!(0, PENDING_RAYS.with(|rays| rays.get()));
+    }
+}
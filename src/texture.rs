@@ -0,0 +1,197 @@
+use crate::error::RenderError;
+use crate::image::{colors, Color};
+use crate::vec::Point;
+
+/// Color varying over a surface
+pub trait Texture: std::fmt::Debug + Send + Sync {
+    /// Color at surface coordinates (`u`, `v`) of the point `p`
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SolidColor(pub Color);
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point) -> Color {
+        self.0
+    }
+}
+
+/// 3D checkerboard, alternating `odd` and `even` every `PI / scale` units
+#[derive(Debug)]
+pub struct Checker {
+    pub odd: Box<dyn Texture>,
+    pub even: Box<dyn Texture>,
+    pub scale: f64,
+}
+
+impl Checker {
+    pub fn new(odd: Box<dyn Texture>, even: Box<dyn Texture>, scale: f64) -> Self {
+        Self { odd, even, scale }
+    }
+
+    pub fn colors(odd: Color, even: Color, scale: f64) -> Self {
+        Self::new(Box::new(SolidColor(odd)), Box::new(SolidColor(even)), scale)
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let sines = (self.scale * p.x).sin() * (self.scale * p.y).sin() * (self.scale * p.z).sin();
+        if sines < 0.0 {
+            self.odd.value(u, v, p)
+        } else {
+            self.even.value(u, v, p)
+        }
+    }
+}
+
+/// Picture mapped on the surface, u going right and v going up
+#[derive(Debug)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    /// linear colors, top to bottom
+    data: Vec<Color>,
+}
+
+impl ImageTexture {
+    pub fn open(path: &str) -> Result<Self, RenderError> {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+        if extension.eq_ignore_ascii_case("hdr") {
+            return Self::open_hdr(path);
+        }
+        let img = ::image::open(path)
+            .map_err(|e| RenderError::Decode(format!("{}: {}", path, e)))?
+            .to_rgb8();
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let data = img
+            .into_raw()
+            .chunks(3)
+            .map(|rgb| {
+                // pictures are encoded with a gamma of 2.2, as the renders
+                let c = Color::from_rgb8([rgb[0], rgb[1], rgb[2]]);
+                Color::new(c.red.powf(2.2), c.green.powf(2.2), c.blue.powf(2.2))
+            })
+            .collect();
+        Ok(Self::from_colors(width, height, data))
+    }
+
+    // Radiance pictures hold linear colors going past 1, kept as they are
+    fn open_hdr(path: &str) -> Result<Self, RenderError> {
+        let invalid = |e: ::image::ImageError| RenderError::Decode(format!("{}: {}", path, e));
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let decoder = ::image::codecs::hdr::HdrDecoder::new(file).map_err(invalid)?;
+        let size = decoder.metadata();
+        let data = decoder
+            .read_image_hdr()
+            .map_err(invalid)?
+            .iter()
+            .map(|px| Color::new(px.0[0] as f64, px.0[1] as f64, px.0[2] as f64))
+            .collect();
+        Ok(Self::from_colors(
+            size.width as usize,
+            size.height as usize,
+            data,
+        ))
+    }
+
+    /// Picture of `width` by `height` linear colors, top to bottom
+    pub fn from_colors(width: usize, height: usize, data: Vec<Color>) -> Self {
+        assert_eq!(width * height, data.len(), "Picture size mismatch");
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point) -> Color {
+        if self.data.is_empty() {
+            return colors::MAGENTA;
+        }
+        // coordinates are clamped to the picture, NaN going to 0 as clamp
+        // lets it through
+        let unit = |x: f64| if x.is_nan() { 0.0 } else { x.clamp(0.0, 1.0) };
+        let u = unit(u);
+        // rows are stored from the top
+        let v = 1.0 - unit(v);
+        let col = ((u * self.width as f64) as usize).min(self.width - 1);
+        let line = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.data[line * self.width + col]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn checker_alternates_along_x() {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let white = Color::new(1.0, 1.0, 1.0);
+        // squares of one unit
+        let checker = Checker::colors(black, white, std::f64::consts::PI);
+        let red = |x: f64| checker.value(0.0, 0.0, &Point::new(x, 0.5, 0.5)).red;
+        assert_eq!(1.0, red(0.5));
+        assert_eq!(0.0, red(1.5));
+        assert_eq!(1.0, red(2.5));
+        assert_eq!(0.0, red(-0.5));
+        // same square whatever the surface coordinates
+        assert_eq!(
+            1.0,
+            checker.value(0.3, 0.9, &Point::new(0.25, 0.5, 0.5)).red
+        );
+    }
+
+    #[test]
+    fn image_texture_corners() {
+        // red green on top, blue white at the bottom
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/checker_2x2.png");
+        let texture = ImageTexture::open(path).unwrap();
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let rgb = |u: f64, v: f64| {
+            let c = texture.value(u, v, &origin);
+            (c.red, c.green, c.blue)
+        };
+        assert_eq!((0.0, 0.0, 1.0), rgb(0.0, 0.0));
+        assert_eq!((1.0, 1.0, 1.0), rgb(1.0, 0.0));
+        assert_eq!((1.0, 0.0, 0.0), rgb(0.0, 1.0));
+        assert_eq!((0.0, 1.0, 0.0), rgb(1.0, 1.0));
+        // out of range coordinates stick to the edges
+        assert_eq!((0.0, 1.0, 0.0), rgb(7.0, 1.5));
+        assert_eq!((0.0, 0.0, 1.0), rgb(-3.0, f64::NAN));
+        assert_eq!((0.0, 0.0, 1.0), rgb(f64::NAN, 0.0));
+    }
+
+    #[test]
+    fn hdr_pictures_keep_their_range() {
+        // bright red orange on the left, brighter blue on the right
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/sky_2x1.hdr");
+        let texture = ImageTexture::open(path).unwrap();
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let rgb = |u: f64| {
+            let c = texture.value(u, 0.5, &origin);
+            (c.red, c.green, c.blue)
+        };
+        assert_eq!((4.0, 0.5, 0.0), rgb(0.25));
+        assert_eq!((0.0, 0.0, 16.0), rgb(0.75));
+        assert!(ImageTexture::open("does-not-exist.hdr").is_err());
+    }
+
+    #[test]
+    fn missing_or_empty_images() {
+        assert!(ImageTexture::open("does-not-exist.png").is_err());
+        let empty = ImageTexture {
+            width: 0,
+            height: 0,
+            data: Vec::new(),
+        };
+        let c = empty.value(0.5, 0.5, &Point::new(0.0, 0.0, 0.0));
+        assert_eq!((1.0, 0.0, 1.0), (c.red, c.green, c.blue));
+    }
+}
@@ -0,0 +1,164 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::{HitRecord, Hittable, Ray};
+use crate::vec::{self, Point, Vector};
+use std::sync::Arc;
+
+// thickness given to the boxes of triangles lying in an axis plane
+const BOX_PADDING: f64 = 1e-4;
+
+/// Triangle between three vertices, counterclockwise when seen from the
+/// front. Meshes share one material among all their triangles.
+#[derive(Debug)]
+pub struct Triangle {
+    pub a: Point,
+    pub b: Point,
+    pub c: Point,
+    pub material: Arc<Box<dyn Material>>,
+    /// Unit normals at `a`, `b` and `c`, blended across the triangle to
+    /// shade it smoothly instead of with the flat face normal
+    pub normals: Option<[Vector; 3]>,
+}
+
+impl Triangle {
+    pub fn new(a: Point, b: Point, c: Point, material: Arc<Box<dyn Material>>) -> Self {
+        Triangle {
+            a,
+            b,
+            c,
+            material,
+            normals: None,
+        }
+    }
+
+    pub fn with_normals(self, na: Vector, nb: Vector, nc: Vector) -> Self {
+        Triangle {
+            normals: Some([vec::unit(&na), vec::unit(&nb), vec::unit(&nc)]),
+            ..self
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_range: Interval) -> Option<HitRecord> {
+        // Möller-Trumbore: solve origin + t * direction = a + u * ab + v * ac
+        let (ab, ac) = (self.b - self.a, self.c - self.a);
+        let p = vec::cross(&ray.direction, &ac);
+        let det = vec::dot(&ab, &p);
+        if det.abs() < 1e-12 {
+            // parallel to the triangle plane
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let ao = ray.origin - self.a;
+        let u = vec::dot(&ao, &p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = vec::cross(&ao, &ab);
+        let v = vec::dot(&ray.direction, &q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = vec::dot(&ac, &q) * inv_det;
+        if !t_range.surrounds(t) {
+            return None;
+        }
+        let face_normal = vec::cross(&ab, &ac);
+        // the side is given by the face, vertex normals only change shading
+        let front = vec::dot(&face_normal, &ray.direction) < 0.0;
+        // u and v are the barycentric weights of b and c
+        let normal = match &self.normals {
+            Some([na, nb, nc]) => vec::unit(&((1.0 - u - v) * na + u * nb + v * nc)),
+            None => vec::unit(&face_normal),
+        };
+        Some(HitRecord::new(ray.at(t), normal, t, front, &self.material).with_uv(u, v))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let bbox = Aabb::surrounding(
+            &Aabb::from_points(&self.a, &self.b),
+            &Aabb::from_points(&self.c, &self.c),
+        );
+        let pad = |i: Interval| {
+            if i.size() < BOX_PADDING {
+                i.expand(BOX_PADDING)
+            } else {
+                i
+            }
+        };
+        Aabb::new(pad(bbox.x), pad(bbox.y), pad(bbox.z))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::test::gray;
+    use crate::vec::Vector;
+
+    // in the z = -1 plane, facing +z
+    fn triangle() -> Triangle {
+        Triangle::new(
+            Point::new(-1.0, -1.0, -1.0),
+            Point::new(1.0, -1.0, -1.0),
+            Point::new(0.0, 1.0, -1.0),
+            Arc::new(gray()),
+        )
+    }
+
+    #[test]
+    fn front_and_back_hits() {
+        let triangle = triangle();
+        let ahead = Interval::new(0.001, f64::MAX);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = triangle.hit(&ray, ahead).unwrap();
+        assert!((hit.t - 1.0).abs() < 1e-12);
+        assert!(hit.front_face);
+        assert_eq!(Vector::new(0.0, 0.0, 1.0), hit.normal);
+        let back = Ray::new(Point::new(0.0, 0.0, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = triangle.hit(&back, ahead).unwrap();
+        assert!(!hit.front_face);
+        assert_eq!(Vector::new(0.0, 0.0, -1.0), hit.normal);
+    }
+
+    #[test]
+    fn misses_outside_edges() {
+        let triangle = triangle();
+        let ahead = Interval::new(0.001, f64::MAX);
+        let corner = Ray::new(Point::new(0.9, 0.9, 0.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(triangle.hit(&corner, ahead).is_none());
+        let parallel = Ray::new(Point::new(0.0, 0.0, -1.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(triangle.hit(&parallel, ahead).is_none());
+        let bbox = triangle.bounding_box();
+        assert!(bbox.z.size() > 0.0);
+    }
+
+    #[test]
+    fn vertex_normals_are_interpolated() {
+        let (na, nb, nc) = (
+            Vector::new(-1.0, 0.0, 1.0),
+            Vector::new(1.0, 0.0, 1.0),
+            Vector::new(0.0, 1.0, 1.0),
+        );
+        let smooth = triangle().with_normals(na, nb, nc);
+        let centroid = (smooth.a + smooth.b + smooth.c) / 3.0;
+        let ray = Ray::new(
+            Point::new(centroid.x, centroid.y, 0.0),
+            Vector::new(0.0, 0.0, -1.0),
+        );
+        let hit = smooth.hit(&ray, Interval::new(0.001, f64::MAX)).unwrap();
+        let average = vec::unit(&(vec::unit(&na) + vec::unit(&nb) + vec::unit(&nc)));
+        assert!(hit.normal.approx_eq(&average, 1e-12));
+        assert!(hit.front_face);
+        // from behind, the blended normal is flipped toward the ray
+        let back = Ray::new(
+            Point::new(centroid.x, centroid.y, -2.0),
+            Vector::new(0.0, 0.0, 1.0),
+        );
+        let hit = smooth.hit(&back, Interval::new(0.001, f64::MAX)).unwrap();
+        assert!(!hit.front_face);
+        assert!(hit.normal.approx_eq(&-average, 1e-12));
+    }
+}
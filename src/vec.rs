@@ -1,6 +1,7 @@
-use rand::{self, Rng};
+use crate::rng;
+use rand::Rng;
 use std::cmp::PartialEq;
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vector {
@@ -14,6 +15,15 @@ impl Vector {
         Vector { x, y, z }
     }
 
+    /// Vector with all three components set to `v`
+    pub fn splat(v: f64) -> Self {
+        Vector::new(v, v, v)
+    }
+
+    pub fn as_array(&self) -> [f64; 3] {
+        [self.x, self.y, self.z]
+    }
+
     pub fn length(&self) -> f64 {
         self.length_squared().sqrt()
     }
@@ -21,6 +31,79 @@ impl Vector {
     pub fn length_squared(&self) -> f64 {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
+
+    /// Linear interpolation from this vector at `t` = 0 to `other` at 1,
+    /// `t` being clamped to [0, 1]
+    pub fn lerp(&self, other: &Vector, t: f64) -> Vector {
+        self.lerp_unclamped(other, t.clamp(0.0, 1.0))
+    }
+
+    /// Linear interpolation, extrapolating for `t` outside of [0, 1]
+    pub fn lerp_unclamped(&self, other: &Vector, t: f64) -> Vector {
+        (1.0 - t) * self + t * other
+    }
+
+    /// Component-wise equality within `eps`
+    pub fn approx_eq(&self, other: &Vector, eps: f64) -> bool {
+        (self.x - other.x).abs() <= eps
+            && (self.y - other.y).abs() <= eps
+            && (self.z - other.z).abs() <= eps
+    }
+
+    /// All components are close enough to zero that normalizing would blow up
+    pub fn near_zero(&self) -> bool {
+        const EPS: f64 = 1e-8;
+        self.x.abs() < EPS && self.y.abs() < EPS && self.z.abs() < EPS
+    }
+
+    /// Tangent and bitangent completing this unit vector into a right-handed
+    /// orthonormal basis, following Duff et al. "Building an Orthonormal
+    /// Basis, Revisited" which has no singularity around the axes
+    pub fn build_orthonormal_basis(&self) -> (Vector, Vector) {
+        let sign = 1.0f64.copysign(self.z);
+        let a = -1.0 / (sign + self.z);
+        let b = self.x * self.y * a;
+        (
+            Vector::new(1.0 + sign * self.x * self.x * a, sign * b, -sign * self.x),
+            Vector::new(b, sign + self.y * self.y * a, -self.y),
+        )
+    }
+}
+
+impl Index<usize> for Vector {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vector axis {} out of range 0..3", axis),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vector {
+    fn index_mut(&mut self, axis: usize) -> &mut f64 {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Vector axis {} out of range 0..3", axis),
+        }
+    }
+}
+
+impl From<[f64; 3]> for Vector {
+    fn from(a: [f64; 3]) -> Self {
+        Vector::new(a[0], a[1], a[2])
+    }
+}
+
+impl From<Vector> for [f64; 3] {
+    fn from(v: Vector) -> Self {
+        v.as_array()
+    }
 }
 
 impl Neg for &Vector {
@@ -69,6 +152,14 @@ impl Add<Vector> for &Vector {
     }
 }
 
+impl AddAssign for Vector {
+    fn add_assign(&mut self, other: Vector) {
+        self.x += other.x;
+        self.y += other.y;
+        self.z += other.z;
+    }
+}
+
 impl Sub<&Vector> for &Vector {
     type Output = Vector;
 
@@ -125,6 +216,14 @@ impl Mul<f64> for Vector {
     }
 }
 
+impl MulAssign<f64> for Vector {
+    fn mul_assign(&mut self, rhs: f64) {
+        self.x *= rhs;
+        self.y *= rhs;
+        self.z *= rhs;
+    }
+}
+
 impl Mul<Vector> for f64 {
     type Output = Vector;
 
@@ -157,10 +256,41 @@ impl Div<f64> for Vector {
     }
 }
 
+impl DivAssign<f64> for Vector {
+    fn div_assign(&mut self, rhs: f64) {
+        *self *= 1.0 / rhs;
+    }
+}
+
 pub fn dot(a: &Vector, b: &Vector) -> f64 {
     a.x * b.x + a.y * b.y + a.z * b.z
 }
 
+/// Dot product as accurate as if computed in twice the precision, for large
+/// coordinates where the products cancel out. Rounding errors of the products
+/// are recovered exactly with `mul_add`, those of the sums with TwoSum
+/// (Ogita, Rump and Oishi "Accurate Sum and Dot Product"). Several times
+/// slower than `dot`, which is kept for the rendering loop.
+pub fn dot_fma(a: &Vector, b: &Vector) -> f64 {
+    let two_product = |x: f64, y: f64| {
+        let p = x * y;
+        (p, x.mul_add(y, -p))
+    };
+    let two_sum = |x: f64, y: f64| {
+        let s = x + y;
+        let z = s - x;
+        (s, (x - (s - z)) + (y - z))
+    };
+    let (mut sum, mut error) = two_product(a.x, b.x);
+    for (x, y) in [(a.y, b.y), (a.z, b.z)].iter() {
+        let (p, p_err) = two_product(*x, *y);
+        let (s, s_err) = two_sum(sum, p);
+        sum = s;
+        error += p_err + s_err;
+    }
+    sum + error
+}
+
 pub fn cross(a: &Vector, b: &Vector) -> Vector {
     Vector {
         x: a.y * b.z - a.z * b.y,
@@ -175,24 +305,25 @@ pub fn unit(v: &Vector) -> Vector {
 
 pub fn random_unit_vector() -> Vector {
     // by fixing one coordinate and an angle
-    let teta: f64 = rand::thread_rng().gen_range(0.0, 2.0 * std::f64::consts::PI);
-    let z: f64 = rand::thread_rng().gen_range(-1.0, 1.0);
+    let teta: f64 = rng::local().gen_range(0.0, 2.0 * std::f64::consts::PI);
+    let z: f64 = rng::local().gen_range(-1.0, 1.0);
     // a unit vector has equation x² + y² + z² = 1
     // thus x² + y² = 1 - z², given x² + y² = Rxy²
     // with Rxy the radius of circle at "height" z
     let r: f64 = (1.0 - z * z).sqrt();
     Vector::new(r * teta.cos(), r * teta.sin(), z)
 }
+/// Uniform point in the unit disk of the xy plane
 pub fn random_in_unit_disk() -> Vector {
-    let x = rand::thread_rng().gen_range(-1.0, 1.0);
-    let x_square = x * x;
-    let y = loop {
-        let guess = rand::thread_rng().gen_range(0.0, 1.0);
-        if x_square + guess * guess < 1.0 {
-            break guess;
+    let mut rng = rng::local();
+    // rejection sampling in the enclosing square
+    loop {
+        let x = rng.gen_range(-1.0, 1.0);
+        let y = rng.gen_range(-1.0, 1.0);
+        if x * x + y * y < 1.0 {
+            return Vector::new(x, y, 0.0);
         }
-    };
-    Vector::new(x, y, 0.0)
+    }
 }
 
 pub fn reflect(v: &Vector, normal: &Vector) -> Vector {
@@ -201,6 +332,23 @@ pub fn reflect(v: &Vector, normal: &Vector) -> Vector {
 
 pub type Point = Vector;
 
+/// Assert two vectors are equal within an epsilon, 1e-9 if not given
+#[cfg(test)]
+macro_rules! assert_vec_approx_eq {
+    ($left:expr, $right:expr) => {
+        assert_vec_approx_eq!($left, $right, 1e-9)
+    };
+    ($left:expr, $right:expr, $eps:expr) => {{
+        let (left, right) = (&$left, &$right);
+        assert!(
+            left.approx_eq(right, $eps),
+            "assertion failed: `left ~= right`\n  left: `{:?}`\n right: `{:?}`",
+            left,
+            right
+        );
+    }};
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -215,9 +363,126 @@ mod test {
         assert_eq!(25.0, dot(&a, &b));
     }
     #[test]
+    fn dot_fma_survives_cancellation() {
+        // squares of 2^27 + 1 need 55 bits, they cancel out down to 2^28 + 1
+        let big = (1i64 << 27) + 1;
+        let (x, y, z) = (big, big - 1, 3);
+        let a = Vector::new(x as f64, y as f64, z as f64);
+        let b = Vector::new(x as f64, -y as f64, 1.0);
+        let exact = (x as i128 * x as i128 - y as i128 * y as i128 + z as i128) as f64;
+        assert_eq!(exact, dot_fma(&a, &b));
+        assert!((dot(&a, &b) - exact).abs() > 0.0);
+        // same as dot when no rounding happens
+        let a = Vector::new(1.0, 5.0, 3.0);
+        let b = Vector::new(1.0, 3.0, 3.0);
+        assert_eq!(25.0, dot_fma(&a, &b));
+    }
+    #[test]
+    fn unit_disk_is_centered() {
+        let n = 10_000;
+        let points: Vec<Vector> = (0..n).map(|_| random_in_unit_disk()).collect();
+        assert!(points
+            .iter()
+            .all(|p| p.length_squared() < 1.0 && p.z == 0.0));
+        let mean = points
+            .iter()
+            .fold(Vector::new(0.0, 0.0, 0.0), |sum, p| sum + *p)
+            / n as f64;
+        // standard deviation of the mean is 0.5 / sqrt(n) = 0.005
+        assert!(mean.x.abs() < 0.025);
+        assert!(mean.y.abs() < 0.025);
+        // each half of the disk gets its share
+        let below = points.iter().filter(|p| p.y < 0.0).count();
+        assert!((below as f64 / n as f64 - 0.5).abs() < 0.03);
+    }
+    #[test]
     fn cross_example() {
         let u = Vector::new(2., 3., 4.);
         let v = Vector::new(5., 6., 7.);
-        assert_eq!(Vector::new(-3., 6., -3.), cross(&u, &v))
+        assert_vec_approx_eq!(Vector::new(-3., 6., -3.), cross(&u, &v))
+    }
+    #[test]
+    fn approx_eq_within_eps() {
+        let v = Vector::new(1.0, 2.0, 3.0);
+        assert!(v.approx_eq(&Vector::new(1.0 + 1e-10, 2.0, 3.0 - 1e-10), 1e-9));
+        assert!(!v.approx_eq(&Vector::new(1.0, 2.0 + 1e-8, 3.0), 1e-9));
+        assert_vec_approx_eq!(v, Vector::new(1.05, 1.95, 3.0), 0.1);
+    }
+    #[test]
+    fn orthonormal_basis_around_normals() {
+        let mut normals: Vec<Vector> = (0..1000).map(|_| random_unit_vector()).collect();
+        // near and on the axes, on both sides of the z sign flip
+        for &eps in &[0.0, 1e-9, -1e-9, 1e-4] {
+            normals.push(unit(&Vector::new(eps, eps, 1.0)));
+            normals.push(unit(&Vector::new(eps, eps, -1.0)));
+            normals.push(unit(&Vector::new(1.0, eps, eps)));
+            normals.push(unit(&Vector::new(eps, -1.0, eps)));
+            normals.push(unit(&Vector::new(eps, 1.0, -eps)));
+        }
+        for n in &normals {
+            let (t, b) = n.build_orthonormal_basis();
+            assert!((t.length() - 1.0).abs() < 1e-9, "{:?}", n);
+            assert!((b.length() - 1.0).abs() < 1e-9, "{:?}", n);
+            assert!(dot(&t, &b).abs() < 1e-9, "{:?}", n);
+            assert!(dot(&t, n).abs() < 1e-9, "{:?}", n);
+            assert!(dot(&b, n).abs() < 1e-9, "{:?}", n);
+            assert_vec_approx_eq!(*n, cross(&t, &b));
+        }
+    }
+    #[test]
+    #[should_panic]
+    fn approx_eq_macro_fails_beyond_eps() {
+        assert_vec_approx_eq!(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1e-3));
+    }
+
+    #[test]
+    fn compound_assignment_matches_operators() {
+        let v = Vector::new(1.0, -2.0, 3.5);
+        let w = Vector::new(0.5, 4.0, -1.0);
+        let mut sum = v;
+        sum += w;
+        assert_eq!(v + w, sum);
+        let mut scaled = v;
+        scaled *= 3.0;
+        assert_eq!(v * 3.0, scaled);
+        let mut halved = v;
+        halved /= 2.0;
+        assert_eq!(v / 2.0, halved);
+    }
+
+    #[test]
+    fn components_by_axis() {
+        let mut v = Vector::new(1.0, 2.0, 3.0);
+        assert_eq!((1.0, 2.0, 3.0), (v[0], v[1], v[2]));
+        v[1] = -4.0;
+        v[2] += 1.0;
+        assert_eq!(Vector::new(1.0, -4.0, 4.0), v);
+    }
+
+    #[test]
+    #[should_panic(expected = "Vector axis 3 out of range")]
+    fn axis_out_of_range() {
+        let _ = Vector::new(1.0, 2.0, 3.0)[3];
+    }
+
+    #[test]
+    fn lerp_ends_and_middle() {
+        let a = Vector::new(0.0, 2.0, -4.0);
+        let b = Vector::new(1.0, 4.0, 4.0);
+        assert_eq!(a, a.lerp(&b, 0.0));
+        assert_eq!(b, a.lerp(&b, 1.0));
+        assert_eq!(Vector::new(0.5, 3.0, 0.0), a.lerp(&b, 0.5));
+        assert_eq!(b, a.lerp(&b, 3.0));
+        assert_eq!(Vector::new(2.0, 6.0, 12.0), a.lerp_unclamped(&b, 2.0));
+    }
+
+    #[test]
+    fn array_round_trip() {
+        let v = Vector::from([1.0, -2.0, 3.5]);
+        assert_eq!(Vector::new(1.0, -2.0, 3.5), v);
+        assert_eq!([1.0, -2.0, 3.5], v.as_array());
+        let a: [f64; 3] = v.into();
+        assert_eq!([1.0, -2.0, 3.5], a);
+        assert_eq!(Vector::new(2.0, 2.0, 2.0), Vector::splat(2.0));
     }
 }